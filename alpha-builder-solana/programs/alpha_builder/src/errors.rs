@@ -44,4 +44,130 @@ pub enum AlphaError {
     SessionKeyWalletMismatch,
     #[msg("Memo exceeds allowed length")]
     MemoTooLong,
+    #[msg("Signer is not a wallet owner")]
+    NotWalletOwner,
+    #[msg("Proposal does not belong to wallet")]
+    ProposalWalletMismatch,
+    #[msg("Proposal is no longer active")]
+    ProposalNotActive,
+    #[msg("Proposal has expired")]
+    ProposalExpired,
+    #[msg("Proposal expiry must be in the future")]
+    InvalidProposalExpiry,
+    #[msg("Owner has already voted on this proposal")]
+    ProposalAlreadyVoted,
+    #[msg("Proposal approvals have not reached the threshold")]
+    ProposalThresholdNotMet,
+    #[msg("Accounts supplied do not match the proposal action")]
+    ProposalAccountMismatch,
+    #[msg("Proposal is still active and cannot be closed")]
+    ProposalStillActive,
+    #[msg("Wallet instructions exceed the configured limits")]
+    InvalidWalletInstruction,
+    #[msg("Wallet instruction targets a program that may not be invoked")]
+    WalletInstructionNotAllowed,
+    #[msg("Wallet instruction references an account that was not supplied")]
+    WalletInstructionAccountMissing,
+    #[msg("Session call modified the wallet treasury account")]
+    SessionTreasuryModified,
+    #[msg("Session token allowances are invalid")]
+    InvalidTokenAllowances,
+    #[msg("Session key is not authorised for this mint")]
+    SessionMintNotAuthorised,
+    #[msg("Spending limit configuration is invalid")]
+    InvalidSpendingLimit,
+    #[msg("Spending limit account must be supplied for this wallet")]
+    SpendingLimitAccountMissing,
+    #[msg("Transfer exceeds the wallet spending limit")]
+    SpendingLimitExceeded,
+    #[msg("Operator configuration is invalid")]
+    InvalidOperatorConfig,
+    #[msg("Transfer exceeds the operator's single transfer limit")]
+    OperatorTransferTooLarge,
+    #[msg("Transfer exceeds the operator's period allowance")]
+    OperatorAllowanceExceeded,
+    #[msg("Destination is not on the operator allowlist")]
+    OperatorDestinationNotAllowed,
+    #[msg("Operator is already configured for this wallet")]
+    OperatorAlreadyConfigured,
+    #[msg("Too many operators configured")]
+    TooManyOperators,
+    #[msg("Operator is not authorised for this action")]
+    OperatorScopeNotAllowed,
+    #[msg("Operator authorisation has expired")]
+    OperatorExpired,
+    #[msg("Address book configuration is invalid")]
+    InvalidAddressBook,
+    #[msg("Address book account must be supplied for this wallet")]
+    AddressBookAccountMissing,
+    #[msg("Address is already in the address book")]
+    AddressBookEntryExists,
+    #[msg("Address is not in the address book")]
+    AddressBookEntryMissing,
+    #[msg("Address book is full")]
+    AddressBookFull,
+    #[msg("Pending transfer account does not match the expected address")]
+    PendingTransferAccountMismatch,
+    #[msg("Pending transfer does not belong to wallet")]
+    PendingTransferWalletMismatch,
+    #[msg("Pending transfer delay has not elapsed")]
+    PendingTransferLocked,
+    #[msg("Transfer timelock configuration is invalid")]
+    InvalidTransferTimelock,
+    #[msg("Signer is neither a cancelling owner nor a guardian")]
+    NotWalletCanceller,
+    #[msg("No transfer timelock update is pending")]
+    TimelockUpdateNotPending,
+    #[msg("Owner quota configuration is invalid")]
+    InvalidOwnerQuota,
+    #[msg("Action threshold is invalid")]
+    InvalidActionThreshold,
+    #[msg("Owner does not hold the role required for this action")]
+    OwnerRoleMissing,
+    #[msg("Owner roles are invalid")]
+    InvalidOwnerRoles,
+    #[msg("Wallet still has open session keys")]
+    SessionsStillOpen,
+    #[msg("Wallet cannot be closed while a transfer timelock is active")]
+    TransferTimelockActive,
+    #[msg("Token accounts to sweep are missing or not held by the treasury")]
+    InvalidSweepAccount,
+    #[msg("Account version is not supported by this program")]
+    UnsupportedAccountVersion,
+    #[msg("Account already uses the current layout")]
+    AccountAlreadyMigrated,
+    #[msg("Wallet capacity must cover current owners and guardians and fit the account layout")]
+    InvalidWalletCapacity,
+    #[msg("Session key was revoked with the rest of its session epoch")]
+    SessionEpochRevoked,
+    #[msg("Wallet treasury cannot cover the rent for this account")]
+    TreasuryRentShortfall,
+    #[msg("Session key is still usable and cannot be closed by anyone")]
+    SessionStillActive,
+    #[msg("Sessions and the operator delegate are frozen")]
+    DelegatesFrozen,
+    #[msg("Signer is neither a wallet owner nor a guardian")]
+    NotOwnerOrGuardian,
+    #[msg("Session key has used its allowance for the current period")]
+    SessionRateLimited,
+    #[msg("Session rate limit needs a period and at least one per-period cap")]
+    InvalidSessionRateLimit,
+    #[msg("Session key may not send to this destination")]
+    SessionDestinationNotAllowed,
+    #[msg("Instruction does not match any of the session's templates")]
+    SessionInstructionNotAllowed,
+    #[msg("Session destination allowlist is too long")]
+    InvalidSessionDestinations,
+    #[msg("Session instruction templates are invalid")]
+    InvalidInstructionTemplate,
+    #[msg("Session registry account is required for this wallet")]
+    SessionRegistryAccountMissing,
+    #[msg("Session registry is full")]
+    SessionRegistryFull,
+    #[msg("Every open session must be passed when creating the session registry")]
+    SessionRegistryIncomplete,
+    #[msg("Pending transfers must be released or cancelled first")]
+    PendingTransfersStillOpen,
+    #[msg("Proposals must be closed first")]
+    ProposalsStillOpen,
 }
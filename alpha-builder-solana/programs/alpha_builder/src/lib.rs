@@ -1,5 +1,10 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
 use anchor_lang::system_program;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_spl::{token_2022, token_interface};
 use errors::AlphaError;
 use state::constants::*;
 use state::*;
@@ -15,6 +20,7 @@ pub mod alpha_builder {
 
     pub fn init_vault(ctx: Context<InitVault>, operator: Option<Pubkey>) -> Result<()> {
         let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.version = VaultConfig::VERSION;
         vault_config.admin = ctx.accounts.admin.key();
         vault_config.operator = operator;
         vault_config.bump = *ctx
@@ -51,6 +57,7 @@ pub mod alpha_builder {
         );
 
         if vault_balance.owner == Pubkey::default() {
+            vault_balance.version = VaultBalance::VERSION;
             vault_balance.owner = owner_key;
             vault_balance.bump = *ctx
                 .bumps
@@ -111,22 +118,9 @@ pub mod alpha_builder {
 
         vault_balance.amount = vault_balance.amount.checked_sub(amount).unwrap();
 
-        let seeds = &[
-            b"vault-treasury",
-            ctx.accounts.vault_config.key().as_ref(),
-            &[ctx.accounts.vault_treasury.bump],
-        ];
-
-        let transfer_accounts = system_program::Transfer {
-            from: ctx.accounts.vault_treasury.to_account_info(),
-            to: ctx.accounts.recipient.to_account_info(),
-        };
-        system_program::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.system_program.to_account_info(),
-                transfer_accounts,
-                &[seeds],
-            ),
+        debit_treasury(
+            &ctx.accounts.vault_treasury.to_account_info(),
+            &ctx.accounts.recipient,
             amount,
         )?;
 
@@ -148,738 +142,4752 @@ pub mod alpha_builder {
         threshold: u16,
         guardians: Vec<Pubkey>,
         guardian_quorum: u8,
-        guardian_cooldown_slots: u64,
+        guardian_cooldown: u64,
+        guardian_cooldown_unit: WindowUnit,
         operator_delegate: Option<Pubkey>,
+        capacity: Option<WalletCapacity>,
     ) -> Result<()> {
-        validate_owner_set(&owners)?;
-        validate_guardians(&guardians, guardian_quorum)?;
-
-        let total_weight: u64 = owners.iter().map(|o| o.weight as u64).sum();
-        require!(!owners.is_empty(), AlphaError::OwnerThresholdNotMet);
-        require!(
-            (threshold as u64) > 0 && (threshold as u64) <= total_weight,
-            AlphaError::OwnerThresholdNotMet
-        );
-
-        let wallet_state = &mut ctx.accounts.wallet_state;
-        wallet_state.owners = owners;
-        wallet_state.threshold = threshold;
-        wallet_state.guardians = GuardianSet {
+        let treasury_bump = *ctx
+            .bumps
+            .get("wallet_treasury")
+            .expect("wallet_treasury bump must exist");
+        initialize_wallet(
+            &ctx.accounts.wallet_state,
+            &mut ctx.accounts.wallet_treasury,
+            treasury_bump,
+            owners,
+            threshold,
             guardians,
-            quorum: guardian_quorum,
-            cooldown_slots: guardian_cooldown_slots,
-            active_recovery: None,
-        };
-        wallet_state.session_nonce = 0;
-        wallet_state.treasury_bump = *ctx
+            guardian_quorum,
+            guardian_cooldown,
+            guardian_cooldown_unit,
+            operator_delegate,
+            capacity,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_derived_wallet(
+        ctx: Context<InitDerivedWallet>,
+        _salt: [u8; 32],
+        owners: Vec<OwnerShare>,
+        threshold: u16,
+        guardians: Vec<Pubkey>,
+        guardian_quorum: u8,
+        guardian_cooldown: u64,
+        guardian_cooldown_unit: WindowUnit,
+        operator_delegate: Option<Pubkey>,
+        capacity: Option<WalletCapacity>,
+    ) -> Result<()> {
+        let treasury_bump = *ctx
             .bumps
             .get("wallet_treasury")
             .expect("wallet_treasury bump must exist");
-        wallet_state.operator_delegate = operator_delegate;
+        initialize_wallet(
+            &ctx.accounts.wallet_state,
+            &mut ctx.accounts.wallet_treasury,
+            treasury_bump,
+            owners,
+            threshold,
+            guardians,
+            guardian_quorum,
+            guardian_cooldown,
+            guardian_cooldown_unit,
+            operator_delegate,
+            capacity,
+        )
+    }
+
+    pub fn add_wallet_operator(
+        ctx: Context<WalletOwnerAuthority>,
+        operator: Pubkey,
+        config: OperatorConfig,
+    ) -> Result<()> {
+        validate_operator_config(&config)?;
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        reserve_wallet_state(&ctx.accounts.wallet_state)?;
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::ConfigureOperator,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        require!(
+            wallet.operator(&operator).is_none(),
+            AlphaError::OperatorAlreadyConfigured
+        );
+
+        let clock = Clock::get()?;
+        wallet.push_operator(OperatorEntry::from_config(operator, config.clone(), &clock))?;
 
-        let wallet_treasury = &mut ctx.accounts.wallet_treasury;
-        wallet_treasury.vault = wallet_state.key();
-        wallet_treasury.bump = wallet_state.treasury_bump;
+        emit!(WalletOperatorUpdated {
+            wallet: wallet_key,
+            operator,
+            config: Some(config),
+        });
+
+        drop(wallet);
+        resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
 
         Ok(())
     }
 
-    pub fn set_wallet_operator(
+    pub fn update_wallet_operator(
         ctx: Context<WalletOwnerAuthority>,
-        new_operator: Option<Pubkey>,
+        operator: Pubkey,
+        config: OperatorConfig,
     ) -> Result<()> {
-        let wallet = &mut ctx.accounts.wallet_state;
-        verify_owner_threshold(wallet, &ctx.accounts.authority, ctx.remaining_accounts)?;
-        wallet.operator_delegate = new_operator;
+        validate_operator_config(&config)?;
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::ConfigureOperator,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let clock = Clock::get()?;
+        let entry = wallet
+            .operator_mut(&operator)
+            .ok_or(AlphaError::OperatorNotConfigured)?;
+        *entry = OperatorEntry::from_config(operator, config.clone(), &clock);
+
+        emit!(WalletOperatorUpdated {
+            wallet: wallet_key,
+            operator,
+            config: Some(config),
+        });
+
         Ok(())
     }
 
-    pub fn register_session_key(
-        ctx: Context<RegisterSessionKey>,
-        config: SessionConfig,
+    pub fn remove_wallet_operator(
+        ctx: Context<WalletOwnerAuthority>,
+        operator: Pubkey,
     ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::ConfigureOperator,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
         require!(
-            config.allowed_programs.len() <= MAX_SESSION_PROGRAMS,
-            AlphaError::SessionProgramNotAuthorised
+            wallet.operator(&operator).is_some(),
+            AlphaError::OperatorNotConfigured
         );
+        wallet.remove_operator(&operator);
 
-        let wallet = &mut ctx.accounts.wallet_state;
-        verify_owner_threshold(wallet, &ctx.accounts.authority, ctx.remaining_accounts)?;
+        emit!(WalletOperatorUpdated {
+            wallet: wallet_key,
+            operator,
+            config: None,
+        });
 
-        let session_account = &mut ctx.accounts.session_account;
-        session_account.wallet = wallet.key();
-        session_account.authority = ctx.accounts.session_authority.key();
-        session_account.expires_at_slot = config.expires_at_slot;
-        session_account.remaining_calls = config.usage_limit;
-        session_account.remaining_value = config.value_limit;
-        session_account.allowed_programs = config.allowed_programs;
-        session_account.bump = *ctx
-            .bumps
-            .get("session_account")
-            .expect("session_account bump must exist");
+        drop(wallet);
+        resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
 
-        wallet.session_nonce = wallet
-            .session_nonce
-            .checked_add(1)
-            .expect("session nonce overflow");
+        Ok(())
+    }
 
-        emit!(SessionKeyRegistered {
-            wallet: wallet.key(),
-            authority: session_account.authority,
-            expires_at_slot: session_account.expires_at_slot,
-            usage_limit: session_account.remaining_calls,
+    pub fn set_spending_limit(
+        ctx: Context<SetSpendingLimit>,
+        max_outflow: u64,
+        window_length: u64,
+        window_unit: WindowUnit,
+        elevated_threshold: u16,
+    ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let spending_limit = &mut ctx.accounts.spending_limit;
+        verify_spending_limit_authority(
+            &wallet,
+            spending_limit,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        require!(window_length > 0, AlphaError::InvalidSpendingLimit);
+        require!(
+            elevated_threshold >= wallet.threshold_for(ActionClass::Transfer)
+                && elevated_threshold as u64 <= wallet.total_weight(),
+            AlphaError::InvalidSpendingLimit
+        );
+
+        if wallet.spending_limit_enabled == 0 {
+            spending_limit.version = SpendingLimit::VERSION;
+            spending_limit.wallet = wallet_key;
+            spending_limit.window_start = window_unit.now(&Clock::get()?);
+            spending_limit.spent_in_window = 0;
+            spending_limit.bump = *ctx
+                .bumps
+                .get("spending_limit")
+                .expect("spending_limit bump must exist");
+        }
+        spending_limit.max_outflow = max_outflow;
+        spending_limit.window_length = window_length;
+        spending_limit.window_unit = window_unit;
+        spending_limit.elevated_threshold = elevated_threshold;
+        wallet.spending_limit_enabled = 1;
+
+        emit!(SpendingLimitUpdated {
+            wallet: wallet_key,
+            max_outflow,
+            window_length,
+            window_unit,
+            elevated_threshold,
         });
 
         Ok(())
     }
 
-    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
-        let wallet = &ctx.accounts.wallet_state;
-        verify_owner_threshold(wallet, &ctx.accounts.authority, ctx.remaining_accounts)?;
+    pub fn remove_spending_limit(ctx: Context<RemoveSpendingLimit>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_spending_limit_authority(
+            &wallet,
+            &ctx.accounts.spending_limit,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        wallet.spending_limit_enabled = 0;
 
-        emit!(SessionKeyRevoked {
-            wallet: wallet.key(),
-            authority: ctx.accounts.session_account.authority,
-        });
+        emit!(SpendingLimitRemoved { wallet: wallet_key });
 
         Ok(())
     }
 
-    pub fn execute_transfer(
-        ctx: Context<WalletTransferOwner>,
-        amount: u64,
-        memo: Option<Vec<u8>>,
+    pub fn set_address_book(
+        ctx: Context<SetAddressBook>,
+        min_delayed_amount: u64,
+        delay_slots: u64,
     ) -> Result<()> {
-        require!(amount > 0, AlphaError::AmountMustBePositive);
-        if let Some(ref memo_bytes) = memo {
-            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        require!(delay_slots > 0, AlphaError::InvalidAddressBook);
+
+        let address_book = &mut ctx.accounts.address_book;
+        if wallet.address_book_enabled == 0 {
+            address_book.version = AddressBook::VERSION;
+            address_book.wallet = wallet_key;
+            address_book.entries = Vec::new();
+            address_book.bump = *ctx
+                .bumps
+                .get("address_book")
+                .expect("address_book bump must exist");
         }
+        address_book.min_delayed_amount = min_delayed_amount;
+        address_book.delay_slots = delay_slots;
+        wallet.address_book_enabled = 1;
 
-        let wallet = &ctx.accounts.wallet_state;
-        verify_owner_threshold(wallet, &ctx.accounts.authority, ctx.remaining_accounts)?;
-        transfer_from_wallet(
-            wallet,
-            &ctx.accounts.wallet_treasury,
-            &ctx.accounts.destination,
-            amount,
-            &ctx.accounts.system_program,
+        emit!(AddressBookPolicyUpdated {
+            wallet: wallet_key,
+            min_delayed_amount,
+            delay_slots,
+        });
+
+        Ok(())
+    }
+
+    pub fn add_address_book_entry(ctx: Context<UpdateAddressBook>, address: Pubkey) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
         )?;
 
-        emit!(WalletTransferEvent {
-            wallet: wallet.key(),
-            actor: ctx.accounts.authority.key(),
-            destination: ctx.accounts.destination.key(),
-            amount,
-            memo,
-            via_session: false,
+        let address_book = &mut ctx.accounts.address_book;
+        require!(
+            !address_book.contains(&address),
+            AlphaError::AddressBookEntryExists
+        );
+        require!(
+            address_book.entries.len() < MAX_ADDRESS_BOOK_ENTRIES,
+            AlphaError::AddressBookFull
+        );
+        address_book.entries.push(address);
+
+        emit!(AddressBookEntryUpdated {
+            wallet: wallet_key,
+            address,
+            added: true,
         });
 
         Ok(())
     }
 
-    pub fn execute_transfer_with_session(
-        ctx: Context<SessionTransfer>,
-        amount: u64,
-        memo: Option<Vec<u8>>,
+    pub fn remove_address_book_entry(
+        ctx: Context<UpdateAddressBook>,
+        address: Pubkey,
     ) -> Result<()> {
-        require!(amount > 0, AlphaError::AmountMustBePositive);
-        if let Some(ref memo_bytes) = memo {
-            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
-        }
-
-        let session_account = &mut ctx.accounts.session_account;
-        let wallet = &ctx.accounts.wallet_state;
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
 
+        let address_book = &mut ctx.accounts.address_book;
         require!(
-            session_account.wallet == wallet.key(),
-            AlphaError::SessionKeyWalletMismatch
-        );
-        require!(
-            session_account.authority == ctx.accounts.session_authority.key(),
-            AlphaError::WithdrawAuthorisationFailed
+            address_book.contains(&address),
+            AlphaError::AddressBookEntryMissing
         );
+        address_book.entries.retain(|entry| *entry != address);
 
-        if let Some(limit) = session_account.remaining_calls {
-            require!(limit > 0, AlphaError::SessionKeyExhausted);
-            session_account.remaining_calls = Some(limit - 1);
-        }
+        emit!(AddressBookEntryUpdated {
+            wallet: wallet_key,
+            address,
+            added: false,
+        });
 
-        if let Some(value_limit) = session_account.remaining_value {
-            require!(value_limit >= amount, AlphaError::InsufficientVaultBalance);
-            session_account.remaining_value = Some(value_limit - amount);
-        }
+        Ok(())
+    }
 
-        if let Some(expiry) = session_account.expires_at_slot {
-            let clock = Clock::get()?;
-            require!(clock.slot <= expiry, AlphaError::SessionKeyExpired);
-        }
+    pub fn remove_address_book(ctx: Context<RemoveAddressBook>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        wallet.address_book_enabled = 0;
+
+        emit!(AddressBookRemoved { wallet: wallet_key });
+
+        Ok(())
+    }
 
-        if !session_account.allowed_programs.is_empty() {
+    pub fn set_action_threshold(
+        ctx: Context<WalletOwnerAuthority>,
+        action: ActionClass,
+        threshold: Option<u16>,
+    ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        // The table can lower every other class, so editing it needs owner-change weight.
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdateOwners,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        if let Some(threshold) = threshold {
             require!(
-                session_account
-                    .allowed_programs
-                    .iter()
-                    .any(|program| *program == system_program::ID),
-                AlphaError::SessionProgramNotAuthorised
+                threshold > 0 && threshold as u64 <= wallet.total_weight(),
+                AlphaError::InvalidActionThreshold
             );
         }
 
-        transfer_from_wallet(
-            wallet,
-            &ctx.accounts.wallet_treasury,
-            &ctx.accounts.destination,
-            amount,
-            &ctx.accounts.system_program,
-        )?;
+        wallet.set_action_threshold(action, threshold);
 
-        emit!(WalletTransferEvent {
-            wallet: wallet.key(),
-            actor: ctx.accounts.session_authority.key(),
-            destination: ctx.accounts.destination.key(),
-            amount,
-            memo,
-            via_session: true,
+        emit!(ActionThresholdUpdated {
+            wallet: wallet_key,
+            action,
+            threshold,
         });
 
         Ok(())
     }
 
-    pub fn operator_transfer(
-        ctx: Context<OperatorTransfer>,
-        amount: u64,
-        memo: Option<Vec<u8>>,
+    pub fn set_owner_roles(
+        ctx: Context<WalletOwnerAuthority>,
+        owner: Pubkey,
+        roles: u8,
     ) -> Result<()> {
-        require!(amount > 0, AlphaError::AmountMustBePositive);
-        if let Some(ref memo_bytes) = memo {
-            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
-        }
+        require!(roles & !OWNER_ROLE_ALL == 0, AlphaError::InvalidOwnerRoles);
 
-        let wallet = &ctx.accounts.wallet_state;
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdateOwners,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
         require!(
-            wallet
-                .operator_delegate
-                .map(|op| op == ctx.accounts.operator.key())
-                .unwrap_or(false),
-            AlphaError::OperatorNotConfigured
+            wallet.owner_weight(&owner).is_some(),
+            AlphaError::NotWalletOwner
         );
 
-        transfer_from_wallet(
-            wallet,
-            &ctx.accounts.wallet_treasury,
-            &ctx.accounts.destination,
-            amount,
-            &ctx.accounts.system_program,
-        )?;
+        wallet.set_owner_roles(&owner, roles);
 
-        emit!(WalletTransferEvent {
-            wallet: wallet.key(),
-            actor: ctx.accounts.operator.key(),
-            destination: ctx.accounts.destination.key(),
-            amount,
-            memo,
-            via_session: false,
+        emit!(OwnerRolesUpdated {
+            wallet: wallet_key,
+            owner,
+            roles,
         });
 
         Ok(())
     }
 
-    pub fn guardian_initiate_recovery(
-        ctx: Context<GuardianAction>,
-        new_threshold: u16,
-        new_owners: Vec<OwnerShare>,
+    pub fn set_wallet_capacity(
+        ctx: Context<WalletOwnerAuthority>,
+        capacity: WalletCapacity,
     ) -> Result<()> {
-        validate_owner_set(&new_owners)?;
-        let wallet = &mut ctx.accounts.wallet_state;
-        ensure_guardian_member(wallet, &ctx.accounts.guardian.key())?;
-        require!(
-            wallet.guardians.active_recovery.is_none(),
-            AlphaError::RecoveryInProgress
-        );
-
-        let total_weight: u64 = new_owners.iter().map(|o| o.weight as u64).sum();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdateOwners,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        validate_wallet_capacity(&capacity)?;
+        let pending_owners = wallet
+            .active_recovery()
+            .map(|recovery| recovery.new_owners().len())
+            .unwrap_or_default();
         require!(
-            (new_threshold as u64) > 0 && (new_threshold as u64) <= total_weight,
-            AlphaError::OwnerThresholdNotMet
+            capacity.max_owners as usize >= wallet.owners().len().max(pending_owners)
+                && capacity.max_guardians as usize >= wallet.guardians().len(),
+            AlphaError::InvalidWalletCapacity
         );
 
-        let clock = Clock::get()?;
-        let execute_after_slot = clock
-            .slot
-            .checked_add(wallet.guardians.cooldown_slots)
-            .expect("slot overflow");
-
-        wallet.guardians.active_recovery = Some(RecoveryProposal {
-            proposed_slot: clock.slot,
-            execute_after_slot,
-            new_threshold,
-            new_owners,
-            approvals: vec![ctx.accounts.guardian.key()],
-        });
+        wallet.capacity = capacity;
 
-        emit!(RecoveryProposed {
-            wallet: wallet.key(),
-            guardian: ctx.accounts.guardian.key(),
-            execute_after_slot,
+        emit!(WalletCapacityUpdated {
+            wallet: wallet_key,
+            capacity,
         });
 
         Ok(())
     }
 
-    pub fn guardian_vote_recovery(ctx: Context<GuardianAction>) -> Result<()> {
-        let wallet = &mut ctx.accounts.wallet_state;
-        let guardian_key = ctx.accounts.guardian.key();
-        ensure_guardian_member(wallet, &guardian_key)?;
-
-        let recovery = wallet
-            .guardians
-            .active_recovery
-            .as_mut()
-            .ok_or(AlphaError::NoActiveRecovery)?;
+    pub fn set_owner_quota(
+        ctx: Context<WalletOwnerAuthority>,
+        owner: Pubkey,
+        quota: Option<OwnerQuotaConfig>,
+    ) -> Result<()> {
+        if let Some(ref quota) = quota {
+            require!(
+                quota.allowance > 0 && quota.period_length > 0,
+                AlphaError::InvalidOwnerQuota
+            );
+        }
 
+        let wallet_key = ctx.accounts.wallet_state.key();
+        reserve_wallet_state(&ctx.accounts.wallet_state)?;
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
         require!(
-            !recovery.approvals.contains(&guardian_key),
-            AlphaError::GuardianAlreadyApproved
+            wallet.owner_weight(&owner).is_some(),
+            AlphaError::NotWalletOwner
         );
-        recovery.approvals.push(guardian_key);
 
-        emit!(RecoveryVote {
-            wallet: wallet.key(),
-            guardian: guardian_key,
-            approvals: recovery.approvals.len() as u8,
+        match quota {
+            Some(config) => {
+                wallet.set_owner_quota(OwnerQuota::from_config(owner, config, &Clock::get()?))
+            }
+            None => wallet.remove_owner_quota(&owner),
+        }
+
+        emit!(OwnerQuotaUpdated {
+            wallet: wallet_key,
+            owner,
+            quota,
         });
 
+        drop(wallet);
+        resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
+
         Ok(())
     }
 
-    pub fn guardian_execute_recovery(ctx: Context<GuardianAction>) -> Result<()> {
-        let wallet = &mut ctx.accounts.wallet_state;
-        ensure_guardian_member(wallet, &ctx.accounts.guardian.key())?;
-        let recovery = wallet
-            .guardians
-            .active_recovery
-            .as_ref()
-            .ok_or(AlphaError::NoActiveRecovery)?
-            .clone();
+    pub fn set_transfer_timelock(
+        ctx: Context<WalletOwnerAuthority>,
+        timelock: Option<TransferTimelock>,
+    ) -> Result<()> {
+        if let Some(ref timelock) = timelock {
+            require!(
+                timelock.delay_slots > 0,
+                AlphaError::InvalidTransferTimelock
+            );
+        }
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
 
         let clock = Clock::get()?;
+        let current = wallet.effective_transfer_timelock(&clock);
+        wallet.set_transfer_timelock(current);
+        wallet.set_scheduled_timelock(None);
+
+        // Weakening an active timelock waits out its own delay so owners and guardians can cancel.
+        let effective_slot = match current {
+            Some(current) if current.weakened_by(timelock.as_ref()) => {
+                let effective_slot = clock.slot.saturating_add(current.delay_slots);
+                wallet.set_scheduled_timelock(Some(ScheduledTimelock {
+                    timelock,
+                    effective_slot,
+                }));
+                effective_slot
+            }
+            _ => {
+                wallet.set_transfer_timelock(timelock);
+                clock.slot
+            }
+        };
+
+        emit!(TransferTimelockUpdated {
+            wallet: wallet_key,
+            timelock,
+            effective_slot,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_transfer_timelock_update(ctx: Context<WalletOwnerAuthority>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
         require!(
-            clock.slot >= recovery.execute_after_slot,
-            AlphaError::RecoveryNotReady
+            wallet.can_cancel(&authority),
+            AlphaError::NotWalletCanceller
         );
 
+        let clock = Clock::get()?;
         require!(
-            recovery.approvals.len() as u8 >= wallet.guardians.quorum,
-            AlphaError::GuardianQuorumNotMet
+            wallet
+                .scheduled_timelock()
+                .map(|scheduled| clock.slot < scheduled.effective_slot)
+                .unwrap_or(false),
+            AlphaError::TimelockUpdateNotPending
         );
+        wallet.set_scheduled_timelock(None);
 
-        wallet.owners = recovery.new_owners;
-        wallet.threshold = recovery.new_threshold;
-        wallet.guardians.active_recovery = None;
-
-        emit!(RecoveryCompleted {
-            wallet: wallet.key(),
-            executed_slot: clock.slot,
+        emit!(TransferTimelockUpdateCancelled {
+            wallet: wallet_key,
+            cancelled_by: authority,
         });
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct InitVault<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(
-        init,
-        payer = admin,
-        space = VaultConfig::LEN,
-        seeds = [b"vault", admin.key().as_ref()],
-        bump
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
-    #[account(
-        init,
-        payer = admin,
+    pub fn register_session_key(
+        ctx: Context<RegisterSessionKey>,
+        config: SessionConfig,
+    ) -> Result<()> {
+        validate_session_config(&config)?;
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::RegisterSession,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let session = new_session_state(
+            &mut wallet,
+            wallet_key,
+            ctx.accounts.session_authority.key(),
+            config,
+            *ctx.bumps
+                .get("session_account")
+                .expect("session_account bump must exist"),
+            &Clock::get()?,
+        );
+        create_session_account(
+            &ctx.accounts.wallet_treasury.to_account_info(),
+            &ctx.accounts.session_account.to_account_info(),
+            &ctx.accounts.system_program,
+            &session,
+        )?;
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.upsert(&session)?;
+        }
+
+        emit!(SessionKeyRegistered {
+            wallet: wallet_key,
+            authority: session.authority,
+            expires_at_slot: session.expires_at.and_then(|deadline| deadline.slot()),
+            expires_at_timestamp: session.expires_at.and_then(|deadline| deadline.timestamp()),
+            usage_limit: session.remaining_calls,
+        });
+
+        Ok(())
+    }
+
+    pub fn init_session_registry(ctx: Context<InitSessionRegistry>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::UpdatePolicy,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let registry = &mut ctx.accounts.session_registry;
+        registry.version = SessionRegistry::VERSION;
+        registry.wallet = wallet_key;
+        registry.entries = Vec::new();
+        registry.bump = *ctx
+            .bumps
+            .get("session_registry")
+            .expect("session_registry bump must exist");
+
+        // Sessions opened before the registry come in `remaining_accounts`; every one must be listed.
+        for info in ctx.remaining_accounts {
+            if *info.owner != crate::ID {
+                continue;
+            }
+            let session = {
+                let data = info.try_borrow_data()?;
+                let mut slice: &[u8] = &data;
+                match SessionKeyAccount::try_deserialize(&mut slice) {
+                    Ok(session) => session,
+                    Err(_) => continue,
+                }
+            };
+            require!(
+                session.version == SessionKeyAccount::VERSION,
+                AlphaError::UnsupportedAccountVersion
+            );
+            require_keys_eq!(
+                session.wallet,
+                wallet_key,
+                AlphaError::SessionKeyWalletMismatch
+            );
+            registry.upsert(&session)?;
+        }
+        require!(
+            registry.entries.len() == wallet.active_sessions as usize,
+            AlphaError::SessionRegistryIncomplete
+        );
+        wallet.session_registry_enabled = 1;
+
+        emit!(SessionRegistryInitialized {
+            wallet: wallet_key,
+            sessions: wallet.active_sessions,
+        });
+
+        Ok(())
+    }
+
+    pub fn request_session(ctx: Context<RequestSession>, config: SessionConfig) -> Result<()> {
+        validate_session_config(&config)?;
+
+        let request = &mut ctx.accounts.session_request;
+        request.version = SessionRequest::VERSION;
+        request.wallet = ctx.accounts.wallet_state.key();
+        request.authority = ctx.accounts.session_authority.key();
+        request.payer = ctx.accounts.payer.key();
+        request.config = config;
+        request.requested_slot = Clock::get()?.slot;
+        request.bump = *ctx
+            .bumps
+            .get("session_request")
+            .expect("session_request bump must exist");
+
+        emit!(SessionRequested {
+            wallet: request.wallet,
+            authority: request.authority,
+            session_request: request.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_session_request(
+        ctx: Context<ApproveSessionRequest>,
+        config: Option<SessionConfig>,
+    ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::RegisterSession,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        // Owners may grant a different configuration than the one requested.
+        let request = &ctx.accounts.session_request;
+        let amended = config.is_some();
+        let config = config.unwrap_or_else(|| request.config.clone());
+        validate_session_config(&config)?;
+
+        let session = new_session_state(
+            &mut wallet,
+            wallet_key,
+            request.authority,
+            config,
+            *ctx.bumps
+                .get("session_account")
+                .expect("session_account bump must exist"),
+            &Clock::get()?,
+        );
+        create_session_account(
+            &ctx.accounts.wallet_treasury.to_account_info(),
+            &ctx.accounts.session_account.to_account_info(),
+            &ctx.accounts.system_program,
+            &session,
+        )?;
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.upsert(&session)?;
+        }
+
+        emit!(SessionKeyRegistered {
+            wallet: wallet_key,
+            authority: session.authority,
+            expires_at_slot: session.expires_at.and_then(|deadline| deadline.slot()),
+            expires_at_timestamp: session.expires_at.and_then(|deadline| deadline.timestamp()),
+            usage_limit: session.remaining_calls,
+        });
+        emit!(SessionRequestResolved {
+            wallet: wallet_key,
+            authority: session.authority,
+            resolved_by: ctx.accounts.authority.key(),
+            approved: true,
+            amended,
+        });
+
+        Ok(())
+    }
+
+    pub fn reject_session_request(ctx: Context<RejectSessionRequest>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        let request = &ctx.accounts.session_request;
+        // Any single owner can turn a request down, and the requesting key can withdraw it.
+        require!(
+            wallet.owner_weight(&authority).is_some() || authority == request.authority,
+            AlphaError::NotWalletOwner
+        );
+
+        emit!(SessionRequestResolved {
+            wallet: wallet_key,
+            authority: request.authority,
+            resolved_by: authority,
+            approved: false,
+            amended: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        // A session authority may always give up its own session.
+        if ctx.accounts.authority.key() != ctx.accounts.session_account.authority {
+            verify_owner_threshold(
+                &wallet,
+                ActionClass::RevokeSession,
+                &ctx.accounts.authority,
+                ctx.remaining_accounts,
+            )?;
+        }
+        wallet.active_sessions = wallet.active_sessions.saturating_sub(1);
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.remove(&ctx.accounts.session_account.authority);
+        }
+
+        emit!(SessionKeyRevoked {
+            wallet: wallet_key,
+            authority: ctx.accounts.session_account.authority,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_expired_session(ctx: Context<CloseExpiredSession>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let session = &ctx.accounts.session_account;
+        let clock = Clock::get()?;
+        let expired = session
+            .expires_at
+            .map(|expiry| expiry.has_passed(&clock))
+            .unwrap_or(false);
+        let exhausted = session.remaining_calls == Some(0);
+        let revoked = session.epoch != wallet.session_nonce;
+        require!(
+            expired || exhausted || revoked,
+            AlphaError::SessionStillActive
+        );
+        wallet.active_sessions = wallet.active_sessions.saturating_sub(1);
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.remove(&session.authority);
+        }
+
+        emit!(SessionKeyRevoked {
+            wallet: wallet_key,
+            authority: session.authority,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_all_sessions(ctx: Context<WalletOwnerAuthority>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::RevokeSession,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        // Older sessions keep their accounts (and their count) until they are revoked one by one.
+        wallet.session_nonce = wallet
+            .session_nonce
+            .checked_add(1)
+            .expect("session nonce overflow");
+
+        emit!(AllSessionsRevoked {
+            wallet: wallet_key,
+            session_epoch: wallet.session_nonce,
+        });
+
+        Ok(())
+    }
+
+    pub fn freeze_delegates(ctx: Context<WalletOwnerAuthority>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let signer = ctx.accounts.authority.key();
+        // Any single owner or guardian can pull the brake during an incident.
+        require!(
+            wallet.owner_weight(&signer).is_some() || wallet.guardians().contains(&signer),
+            AlphaError::NotOwnerOrGuardian
+        );
+        wallet.delegates_frozen = 1;
+
+        emit!(DelegatesFrozen {
+            wallet: wallet_key,
+            frozen_by: signer,
+        });
+
+        Ok(())
+    }
+
+    pub fn unfreeze_delegates(ctx: Context<WalletOwnerAuthority>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let required = wallet
+            .threshold_for(ActionClass::ConfigureOperator)
+            .max(wallet.threshold_for(ActionClass::RegisterSession));
+        require!(
+            owner_signer_weight(&wallet, &ctx.accounts.authority, ctx.remaining_accounts)?
+                >= required as u64,
+            AlphaError::OwnerThresholdNotMet
+        );
+        wallet.delegates_frozen = 0;
+
+        emit!(DelegatesUnfrozen { wallet: wallet_key });
+
+        Ok(())
+    }
+
+    pub fn extend_session(ctx: Context<UpdateSessionKey>, config: SessionConfig) -> Result<()> {
+        validate_session_config(&config)?;
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::RegisterSession,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let session = &mut ctx.accounts.session_account;
+        require!(
+            session.epoch == wallet.session_nonce,
+            AlphaError::SessionEpochRevoked
+        );
+        session.expires_at = config.expires_at;
+        session.remaining_calls = config.usage_limit;
+        session.remaining_value = config.value_limit;
+        session.allowed_programs = config.allowed_programs;
+        session.token_allowances = config.token_allowances;
+        session.allowed_destinations = config.allowed_destinations;
+        session.instruction_templates = config.instruction_templates;
+        let clock = Clock::get()?;
+        session.rate_limit = config
+            .rate_limit
+            .map(|rate_limit| SessionRateLimit::from_config(rate_limit, &clock));
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.upsert(session)?;
+        }
+
+        emit!(SessionKeyExtended {
+            wallet: wallet_key,
+            authority: session.authority,
+            expires_at_slot: session.expires_at.and_then(|deadline| deadline.slot()),
+            expires_at_timestamp: session.expires_at.and_then(|deadline| deadline.timestamp()),
+            usage_limit: session.remaining_calls,
+        });
+
+        Ok(())
+    }
+
+    pub fn rotate_session_authority(ctx: Context<RotateSessionAuthority>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::RegisterSession,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let previous = &ctx.accounts.session_account;
+        require!(
+            previous.epoch == wallet.session_nonce,
+            AlphaError::SessionEpochRevoked
+        );
+        // The session PDA is keyed by its authority, so the state moves to the new address.
+        let mut session = (**previous).clone();
+        session.authority = ctx.accounts.new_session_authority.key();
+        session.bump = *ctx
+            .bumps
+            .get("new_session_account")
+            .expect("new_session_account bump must exist");
+        create_session_account(
+            &ctx.accounts.wallet_treasury.to_account_info(),
+            &ctx.accounts.new_session_account.to_account_info(),
+            &ctx.accounts.system_program,
+            &session,
+        )?;
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.remove(&previous.authority);
+            registry.upsert(&session)?;
+        }
+
+        emit!(SessionAuthorityRotated {
+            wallet: wallet_key,
+            previous_authority: previous.authority,
+            new_authority: ctx.accounts.new_session_authority.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_transfer(
+        ctx: Context<WalletTransferOwner>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let signer_weight =
+            owner_signer_weight(&wallet, &ctx.accounts.authority, ctx.remaining_accounts)?;
+        if signer_weight < wallet.threshold_for(ActionClass::Transfer) as u64 {
+            // Below the threshold a single owner may still spend from their own quota.
+            charge_owner_quota(&mut wallet, &ctx.accounts.authority.key(), amount)?;
+        }
+        enforce_spending_limit(
+            &wallet,
+            ctx.accounts.spending_limit.as_mut(),
+            amount,
+            Some(signer_weight),
+        )?;
+
+        let transfer = WalletTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.authority.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            memo,
+            via_session: false,
+        };
+        if let Some((reason, delay_slots)) = transfer_delay(
+            &wallet,
+            ctx.accounts.address_book.as_ref(),
+            &transfer.destination,
+            amount,
+        )? {
+            return queue_pending_transfer(
+                &mut wallet,
+                &ctx.accounts.authority.to_account_info(),
+                ctx.accounts.pending_transfer.as_ref(),
+                &ctx.accounts.system_program,
+                transfer,
+                reason,
+                delay_slots,
+            );
+        }
+
+        transfer_from_wallet(
+            wallet_key,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.destination,
+            amount,
+        )?;
+        emit!(transfer);
+
+        Ok(())
+    }
+
+    pub fn execute_transfer_with_session(
+        ctx: Context<SessionTransfer>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let session_account = &mut ctx.accounts.session_account;
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+
+        consume_session_call(
+            session_account,
+            &wallet,
+            &wallet_key,
+            &ctx.accounts.session_authority.key(),
+        )?;
+        charge_session_value(session_account, amount)?;
+        require!(
+            session_account.allowed_programs.is_empty()
+                || session_account
+                    .allowed_programs
+                    .contains(&system_program::ID),
+            AlphaError::SessionProgramNotAuthorised
+        );
+        require!(
+            session_account.allows_destination(&[ctx.accounts.destination.key()]),
+            AlphaError::SessionDestinationNotAllowed
+        );
+        enforce_spending_limit(&wallet, ctx.accounts.spending_limit.as_mut(), amount, None)?;
+
+        let transfer = WalletTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.session_authority.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            memo,
+            via_session: true,
+        };
+        if let Some((reason, delay_slots)) = transfer_delay(
+            &wallet,
+            ctx.accounts.address_book.as_ref(),
+            &transfer.destination,
+            amount,
+        )? {
+            return queue_pending_transfer(
+                &mut wallet,
+                &ctx.accounts.session_authority.to_account_info(),
+                ctx.accounts.pending_transfer.as_ref(),
+                &ctx.accounts.system_program,
+                transfer,
+                reason,
+                delay_slots,
+            );
+        }
+
+        transfer_from_wallet(
+            wallet_key,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.destination,
+            amount,
+        )?;
+        emit!(transfer);
+
+        Ok(())
+    }
+
+    pub fn operator_transfer(
+        ctx: Context<OperatorTransfer>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let operator = resolve_operator(
+            &mut wallet,
+            &ctx.accounts.operator.key(),
+            OPERATOR_SCOPE_TRANSFER,
+        )?;
+        if let Some(policy) = operator.policy_mut() {
+            enforce_operator_policy(policy, &ctx.accounts.destination.key(), amount)?;
+        }
+        enforce_spending_limit(&wallet, ctx.accounts.spending_limit.as_mut(), amount, None)?;
+
+        let transfer = WalletTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.operator.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            memo,
+            via_session: false,
+        };
+        if let Some((reason, delay_slots)) = transfer_delay(
+            &wallet,
+            ctx.accounts.address_book.as_ref(),
+            &transfer.destination,
+            amount,
+        )? {
+            return queue_pending_transfer(
+                &mut wallet,
+                &ctx.accounts.operator.to_account_info(),
+                ctx.accounts.pending_transfer.as_ref(),
+                &ctx.accounts.system_program,
+                transfer,
+                reason,
+                delay_slots,
+            );
+        }
+
+        transfer_from_wallet(
+            wallet_key,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.destination,
+            amount,
+        )?;
+        emit!(transfer);
+
+        Ok(())
+    }
+
+    pub fn release_pending_transfer(ctx: Context<ReleasePendingTransfer>) -> Result<()> {
+        // Anyone may release once the delay has passed; owners and guardians cancel before then.
+        let pending = &ctx.accounts.pending_transfer;
+        let clock = Clock::get()?;
+        require!(
+            clock.slot >= pending.release_slot,
+            AlphaError::PendingTransferLocked
+        );
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        transfer_from_wallet(
+            wallet_key,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.destination.to_account_info(),
+            pending.amount,
+        )?;
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        wallet.open_pending_transfers = wallet.open_pending_transfers.saturating_sub(1);
+
+        emit!(PendingTransferReleased {
+            wallet: wallet_key,
+            pending_transfer: pending.key(),
+            index: pending.index,
+            destination: pending.destination,
+            amount: pending.amount,
+            reason: pending.reason,
+        });
+        emit!(WalletTransferEvent {
+            wallet: wallet_key,
+            actor: pending.requested_by,
+            destination: pending.destination,
+            amount: pending.amount,
+            memo: pending.memo.clone(),
+            via_session: pending.via_session,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_pending_transfer(ctx: Context<CancelPendingTransfer>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        require!(
+            wallet.can_cancel(&authority),
+            AlphaError::NotWalletCanceller
+        );
+        wallet.open_pending_transfers = wallet.open_pending_transfers.saturating_sub(1);
+
+        let pending = &ctx.accounts.pending_transfer;
+        emit!(PendingTransferCancelled {
+            wallet: wallet_key,
+            pending_transfer: pending.key(),
+            index: pending.index,
+            destination: pending.destination,
+            amount: pending.amount,
+            reason: pending.reason,
+            cancelled_by: authority,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_treasury_token_account(_ctx: Context<CreateTreasuryTokenAccount>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn close_treasury_token_account(ctx: Context<CloseTreasuryTokenAccount>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::Transfer,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+
+        let seeds = &[
+            b"wallet-treasury",
+            wallet_key.as_ref(),
+            &[wallet.treasury_bump],
+        ];
+        let close_accounts = token::CloseAccount {
+            account: ctx.accounts.treasury_token_account.to_account_info(),
+            destination: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.wallet_treasury.to_account_info(),
+        };
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            close_accounts,
+            &[seeds],
+        ))
+    }
+
+    pub fn execute_token_transfer(
+        ctx: Context<WalletTokenTransferOwner>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::Transfer,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        require_below_transfer_timelock(
+            wallet.effective_transfer_timelock(&Clock::get()?).as_ref(),
+            amount,
+        )?;
+        transfer_tokens_from_wallet(
+            wallet_key,
+            wallet.treasury_bump,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.destination,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        emit!(WalletTokenTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.authority.key(),
+            mint: ctx.accounts.mint.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            decimals: ctx.accounts.mint.decimals,
+            memo,
+            via_session: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_token_transfer_with_session(
+        ctx: Context<SessionTokenTransfer>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let session_account = &mut ctx.accounts.session_account;
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        consume_session_call(
+            session_account,
+            &wallet,
+            &wallet_key,
+            &ctx.accounts.session_authority.key(),
+        )?;
+        require_below_transfer_timelock(
+            wallet.effective_transfer_timelock(&Clock::get()?).as_ref(),
+            amount,
+        )?;
+        require!(
+            session_account.allowed_programs.is_empty()
+                || session_account.allowed_programs.contains(&token::ID),
+            AlphaError::SessionProgramNotAuthorised
+        );
+        require!(
+            session_account.allows_destination(&[
+                ctx.accounts.destination.key(),
+                ctx.accounts.destination.owner
+            ]),
+            AlphaError::SessionDestinationNotAllowed
+        );
+        charge_session_tokens(session_account, &ctx.accounts.mint.key(), amount)?;
+
+        transfer_tokens_from_wallet(
+            wallet_key,
+            wallet.treasury_bump,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.destination,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        emit!(WalletTokenTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.session_authority.key(),
+            mint: ctx.accounts.mint.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            decimals: ctx.accounts.mint.decimals,
+            memo,
+            via_session: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn operator_token_transfer(
+        ctx: Context<OperatorTokenTransfer>,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    ) -> Result<()> {
+        require!(amount > 0, AlphaError::AmountMustBePositive);
+        if let Some(ref memo_bytes) = memo {
+            require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+        }
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let operator = resolve_operator(
+            &mut wallet,
+            &ctx.accounts.operator.key(),
+            OPERATOR_SCOPE_TOKEN_TRANSFER,
+        )?;
+        let policy_bound = operator.policy().is_some();
+        // Policy limits are lamport-denominated and cannot meter tokens, so a policy-bound operator
+        // never holds this scope; operators configured before that rule are refused here.
+        require!(!policy_bound, AlphaError::OperatorScopeNotAllowed);
+        require_below_transfer_timelock(
+            wallet.effective_transfer_timelock(&Clock::get()?).as_ref(),
+            amount,
+        )?;
+
+        transfer_tokens_from_wallet(
+            wallet_key,
+            wallet.treasury_bump,
+            &ctx.accounts.wallet_treasury,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.destination,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        emit!(WalletTokenTransferEvent {
+            wallet: wallet_key,
+            actor: ctx.accounts.operator.key(),
+            mint: ctx.accounts.mint.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            decimals: ctx.accounts.mint.decimals,
+            memo,
+            via_session: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_instructions(
+        ctx: Context<ExecuteInstructions>,
+        instructions: Vec<WalletInstruction>,
+    ) -> Result<()> {
+        validate_wallet_instructions(&instructions)?;
+
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        verify_owner_threshold(
+            &wallet,
+            ActionClass::ExecuteInstructions,
+            &ctx.accounts.authority,
+            ctx.remaining_accounts,
+        )?;
+        let timelock = wallet.effective_transfer_timelock(&Clock::get()?);
+        let treasury_bump = wallet.treasury_bump;
+        // The instructions may reference the wallet account, so release it before the CPIs.
+        drop(wallet);
+
+        invoke_timelocked_instructions(
+            wallet_key,
+            treasury_bump,
+            ctx.accounts.wallet_treasury.key(),
+            timelock.as_ref(),
+            &instructions,
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(WalletInstructionsExecuted {
+            wallet: wallet_key,
+            actor: ctx.accounts.authority.key(),
+            programs: instructions.iter().map(|ix| ix.program_id).collect(),
+            via_session: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_with_session(
+        ctx: Context<SessionExecute>,
+        instructions: Vec<WalletInstruction>,
+    ) -> Result<()> {
+        validate_wallet_instructions(&instructions)?;
+
+        let session_account = &mut ctx.accounts.session_account;
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let (treasury_bump, timelock) = {
+            let wallet = ctx.accounts.wallet_state.load_wallet()?;
+            consume_session_call(
+                session_account,
+                &wallet,
+                &wallet_key,
+                &ctx.accounts.session_authority.key(),
+            )?;
+            (
+                wallet.treasury_bump,
+                wallet.effective_transfer_timelock(&Clock::get()?),
+            )
+        };
+        let treasury_key = ctx.accounts.wallet_treasury.key();
+        let token_accounts = treasury_token_accounts(&treasury_key, ctx.remaining_accounts);
+        // Unlike plain transfers, general CPI requires every program to be listed explicitly.
+        for instruction in &instructions {
+            require!(
+                session_account
+                    .allowed_programs
+                    .contains(&instruction.program_id),
+                AlphaError::SessionProgramNotAuthorised
+            );
+            let accounts = instruction
+                .account_indexes
+                .iter()
+                .map(|index| {
+                    ctx.remaining_accounts
+                        .get(*index as usize)
+                        .map(|info| *info.key)
+                        .ok_or(AlphaError::WalletInstructionAccountMissing)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            require!(
+                session_account.allows_instruction(
+                    &instruction.program_id,
+                    &instruction.data,
+                    &accounts
+                ),
+                AlphaError::SessionInstructionNotAllowed
+            );
+            require_session_destinations(
+                session_account,
+                &treasury_key,
+                &instruction.account_indexes,
+                ctx.remaining_accounts,
+                &token_accounts,
+            )?;
+        }
+
+        // The treasury is owned by this program and holds data, so no invoked program can debit its
+        // lamports, reassign it, or resize it; `value_limit` only meters the transfer instructions.
+        // Its token accounts can be moved with its signature, so those are charged to the allowances
+        // and held to the transfer timelock.
+        invoke_wallet_instructions(
+            wallet_key,
+            treasury_bump,
+            treasury_key,
+            &instructions,
+            ctx.remaining_accounts,
+        )?;
+        let outflows = treasury_token_outflows(
+            ctx.remaining_accounts,
+            &token_accounts,
+            AlphaError::SessionTreasuryModified,
+        )?;
+        for (mint, spent) in &outflows {
+            charge_session_tokens(session_account, mint, *spent)?;
+        }
+        require_token_outflows_below_timelock(timelock.as_ref(), &outflows)?;
+
+        emit!(WalletInstructionsExecuted {
+            wallet: wallet_key,
+            actor: ctx.accounts.session_authority.key(),
+            programs: instructions.iter().map(|ix| ix.program_id).collect(),
+            via_session: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn guardian_initiate_recovery(
+        ctx: Context<GuardianAction>,
+        new_threshold: u16,
+        new_owners: Vec<OwnerShare>,
+    ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        reserve_wallet_state(&ctx.accounts.wallet_state)?;
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        validate_owner_set(&new_owners, wallet.capacity.max_owners)?;
+        ensure_guardian_member(&wallet, &ctx.accounts.guardian.key())?;
+        require!(
+            wallet.active_recovery().is_none(),
+            AlphaError::RecoveryInProgress
+        );
+
+        let total_weight: u64 = new_owners.iter().map(|o| o.weight as u64).sum();
+        require!(
+            (new_threshold as u64) > 0 && (new_threshold as u64) <= total_weight,
+            AlphaError::OwnerThresholdNotMet
+        );
+
+        let clock = Clock::get()?;
+        let execute_after = wallet
+            .guardian_cooldown_unit()
+            .now(&clock)
+            .checked_add(wallet.guardian_cooldown)
+            .expect("cooldown overflow");
+
+        wallet.set_recovery(Some(RecoveryProposal::new(
+            clock.slot,
+            execute_after,
+            new_threshold,
+            &new_owners,
+            &[ctx.accounts.guardian.key()],
+        )));
+        let deadline = wallet.guardian_cooldown_unit().deadline(execute_after);
+
+        emit!(RecoveryProposed {
+            wallet: wallet_key,
+            guardian: ctx.accounts.guardian.key(),
+            execute_after_slot: deadline.slot(),
+            execute_after_timestamp: deadline.timestamp(),
+        });
+
+        drop(wallet);
+        resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
+
+        Ok(())
+    }
+
+    pub fn guardian_vote_recovery(ctx: Context<GuardianAction>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let guardian_key = ctx.accounts.guardian.key();
+        ensure_guardian_member(&wallet, &guardian_key)?;
+
+        let recovery = wallet
+            .active_recovery_mut()
+            .ok_or(AlphaError::NoActiveRecovery)?;
+
+        require!(
+            !recovery.approvals().contains(&guardian_key),
+            AlphaError::GuardianAlreadyApproved
+        );
+        recovery.add_approval(guardian_key);
+
+        emit!(RecoveryVote {
+            wallet: wallet_key,
+            guardian: guardian_key,
+            approvals: recovery.approval_count,
+        });
+
+        Ok(())
+    }
+
+    pub fn guardian_execute_recovery(ctx: Context<GuardianAction>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        reserve_wallet_state(&ctx.accounts.wallet_state)?;
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        ensure_guardian_member(&wallet, &ctx.accounts.guardian.key())?;
+        let recovery = *wallet
+            .active_recovery()
+            .ok_or(AlphaError::NoActiveRecovery)?;
+        let deadline = wallet
+            .recovery_deadline()
+            .ok_or(AlphaError::NoActiveRecovery)?;
+
+        let clock = Clock::get()?;
+        require!(deadline.is_reached(&clock), AlphaError::RecoveryNotReady);
+
+        require!(
+            recovery.approval_count >= wallet.guardian_quorum,
+            AlphaError::GuardianQuorumNotMet
+        );
+
+        wallet.replace_owners(recovery.new_owners(), recovery.new_threshold);
+        wallet.set_recovery(None);
+
+        emit!(RecoveryCompleted {
+            wallet: wallet_key,
+            executed_slot: clock.slot,
+            executed_timestamp: clock.unix_timestamp,
+        });
+
+        drop(wallet);
+        resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
+
+        Ok(())
+    }
+
+    pub fn create_proposal(
+        ctx: Context<CreateProposal>,
+        action: ProposalAction,
+        expires_at_slot: u64,
+    ) -> Result<()> {
+        let proposer = ctx.accounts.proposer.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        validate_proposal_action(&action, wallet.capacity.max_owners)?;
+        ensure_owner_role(&wallet, &proposer, OWNER_ROLE_PROPOSER)?;
+
+        let clock = Clock::get()?;
+        require!(
+            expires_at_slot > clock.slot,
+            AlphaError::InvalidProposalExpiry
+        );
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.version = Proposal::VERSION;
+        proposal.wallet = wallet_key;
+        proposal.index = wallet.proposal_index;
+        proposal.proposer = proposer;
+        proposal.action = action;
+        proposal.status = ProposalStatus::Active;
+        proposal.created_slot = clock.slot;
+        proposal.expires_at_slot = expires_at_slot;
+        proposal.approvals = vec![proposer];
+        proposal.rejections = Vec::new();
+        proposal.bump = *ctx.bumps.get("proposal").expect("proposal bump must exist");
+
+        wallet.proposal_index = wallet
+            .proposal_index
+            .checked_add(1)
+            .expect("proposal index overflow");
+        wallet.open_proposals = wallet
+            .open_proposals
+            .checked_add(1)
+            .expect("open proposal overflow");
+
+        emit!(ProposalCreated {
+            wallet: wallet_key,
+            proposal: proposal.key(),
+            index: proposal.index,
+            proposer,
+            expires_at_slot,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_proposal(ctx: Context<ProposalVote>) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        let proposal = &mut ctx.accounts.proposal;
+
+        record_proposal_vote(&wallet, proposal, owner, true)?;
+
+        emit!(ProposalVoted {
+            wallet: wallet_key,
+            proposal: proposal.key(),
+            owner,
+            approved: true,
+            approval_weight: wallet.weight_of(&proposal.approvals),
+            rejection_weight: wallet.weight_of(&proposal.rejections),
+        });
+
+        Ok(())
+    }
+
+    pub fn reject_proposal(ctx: Context<ProposalVote>) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let wallet = ctx.accounts.wallet_state.load_wallet()?;
+        let proposal = &mut ctx.accounts.proposal;
+
+        record_proposal_vote(&wallet, proposal, owner, false)?;
+
+        let rejection_weight = wallet.weight_of(&proposal.rejections);
+        let required = wallet.threshold_for(proposal.action.class()) as u64;
+        if wallet.total_weight().saturating_sub(rejection_weight) < required {
+            proposal.status = ProposalStatus::Rejected;
+        }
+
+        emit!(ProposalVoted {
+            wallet: wallet_key,
+            proposal: proposal.key(),
+            owner,
+            approved: false,
+            approval_weight: wallet.weight_of(&proposal.approvals),
+            rejection_weight,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let executor = ctx.accounts.executor.key();
+        let wallet_key = ctx.accounts.wallet_state.key();
+        // Operator and owner changes move the wallet's sections, so the account needs room first.
+        let resizes_wallet = matches!(
+            ctx.accounts.proposal.action,
+            ProposalAction::ConfigureOperator { .. } | ProposalAction::UpdateOwners { .. }
+        );
+        if resizes_wallet {
+            reserve_wallet_state(&ctx.accounts.wallet_state)?;
+        }
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        ensure_owner_role(&wallet, &executor, OWNER_ROLE_EXECUTOR)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        let clock = Clock::get()?;
+        ensure_proposal_open(proposal, &clock)?;
+        let approval_weight = wallet.weight_of(&proposal.approvals);
+        require!(
+            approval_weight >= wallet.threshold_for(proposal.action.class()) as u64,
+            AlphaError::ProposalThresholdNotMet
+        );
+        proposal.status = ProposalStatus::Executed;
+
+        match proposal.action.clone() {
+            ProposalAction::Transfer {
+                destination,
+                amount,
+                memo,
+            } => {
+                let destination_info = ctx
+                    .accounts
+                    .destination
+                    .as_ref()
+                    .ok_or(AlphaError::ProposalAccountMismatch)?
+                    .to_account_info();
+                require_keys_eq!(
+                    destination_info.key(),
+                    destination,
+                    AlphaError::ProposalAccountMismatch
+                );
+                enforce_spending_limit(
+                    &wallet,
+                    ctx.accounts.spending_limit.as_mut(),
+                    amount,
+                    Some(approval_weight),
+                )?;
+
+                let transfer = WalletTransferEvent {
+                    wallet: wallet_key,
+                    actor: executor,
+                    destination,
+                    amount,
+                    memo,
+                    via_session: false,
+                };
+                match transfer_delay(
+                    &wallet,
+                    ctx.accounts.address_book.as_ref(),
+                    &destination,
+                    amount,
+                )? {
+                    Some((reason, delay_slots)) => queue_pending_transfer(
+                        &mut wallet,
+                        &ctx.accounts.executor.to_account_info(),
+                        ctx.accounts.pending_transfer.as_ref(),
+                        &ctx.accounts.system_program,
+                        transfer,
+                        reason,
+                        delay_slots,
+                    )?,
+                    None => {
+                        transfer_from_wallet(
+                            wallet_key,
+                            &ctx.accounts.wallet_treasury,
+                            &destination_info,
+                            amount,
+                        )?;
+                        emit!(transfer);
+                    }
+                }
+            }
+            ProposalAction::ConfigureOperator { operator, config } => {
+                match config.clone() {
+                    Some(config) => {
+                        let entry = OperatorEntry::from_config(operator, config, &clock);
+                        match wallet.operator_mut(&operator) {
+                            Some(existing) => *existing = entry,
+                            None => wallet.push_operator(entry)?,
+                        }
+                    }
+                    None => wallet.remove_operator(&operator),
+                }
+
+                emit!(WalletOperatorUpdated {
+                    wallet: wallet_key,
+                    operator,
+                    config,
+                });
+                drop(wallet);
+                resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
+            }
+            ProposalAction::UpdateOwners { owners, threshold } => {
+                validate_owner_set(&owners, wallet.capacity.max_owners)?;
+                validate_threshold(&owners, threshold)?;
+                wallet.replace_owners(&owners, threshold);
+                drop(wallet);
+                resize_wallet_state(&ctx.accounts.wallet_state, &ctx.accounts.wallet_treasury)?;
+            }
+            ProposalAction::RegisterSession { authority, config } => {
+                let session_info = ctx
+                    .accounts
+                    .session_account
+                    .as_ref()
+                    .ok_or(AlphaError::ProposalAccountMismatch)?
+                    .to_account_info();
+                let (expected, bump) = Pubkey::find_program_address(
+                    &[b"session-key", wallet_key.as_ref(), authority.as_ref()],
+                    ctx.program_id,
+                );
+                require_keys_eq!(
+                    session_info.key(),
+                    expected,
+                    AlphaError::ProposalAccountMismatch
+                );
+
+                let session_state =
+                    new_session_state(&mut wallet, wallet_key, authority, config, bump, &clock);
+                create_session_account(
+                    &ctx.accounts.wallet_treasury.to_account_info(),
+                    &session_info,
+                    &ctx.accounts.system_program,
+                    &session_state,
+                )?;
+                if let Some(registry) =
+                    session_registry(&wallet, ctx.accounts.session_registry.as_mut())?
+                {
+                    registry.upsert(&session_state)?;
+                }
+
+                emit!(SessionKeyRegistered {
+                    wallet: wallet_key,
+                    authority,
+                    expires_at_slot: session_state
+                        .expires_at
+                        .and_then(|deadline| deadline.slot()),
+                    expires_at_timestamp: session_state
+                        .expires_at
+                        .and_then(|deadline| deadline.timestamp()),
+                    usage_limit: session_state.remaining_calls,
+                });
+            }
+            ProposalAction::ExecuteInstructions { instructions } => {
+                let timelock = wallet.effective_transfer_timelock(&Clock::get()?);
+                let treasury_bump = wallet.treasury_bump;
+                // The instructions may reference the wallet account, so release it before the CPIs.
+                drop(wallet);
+
+                invoke_timelocked_instructions(
+                    wallet_key,
+                    treasury_bump,
+                    ctx.accounts.wallet_treasury.key(),
+                    timelock.as_ref(),
+                    &instructions,
+                    ctx.remaining_accounts,
+                )?;
+
+                emit!(WalletInstructionsExecuted {
+                    wallet: wallet_key,
+                    actor: executor,
+                    programs: instructions.iter().map(|ix| ix.program_id).collect(),
+                    via_session: false,
+                });
+            }
+        }
+
+        emit!(ProposalExecuted {
+            wallet: wallet_key,
+            proposal: proposal.key(),
+            index: proposal.index,
+            executor,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_proposal(ctx: Context<CloseProposal>) -> Result<()> {
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        require!(
+            wallet.owner_weight(&ctx.accounts.authority.key()).is_some(),
+            AlphaError::NotWalletOwner
+        );
+
+        let proposal = &ctx.accounts.proposal;
+        let clock = Clock::get()?;
+        require!(
+            proposal.status != ProposalStatus::Active || clock.slot > proposal.expires_at_slot,
+            AlphaError::ProposalStillActive
+        );
+        wallet.open_proposals = wallet.open_proposals.saturating_sub(1);
+
+        Ok(())
+    }
+
+    pub fn close_wallet<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloseWallet<'info>>,
+        token_accounts: u8,
+    ) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+
+        // Sweeping the treasury is at least an owner change and a transfer past any spending limit.
+        let mut required = wallet.threshold_for(ActionClass::UpdateOwners) as u64;
+        if wallet.spending_limit_enabled != 0 {
+            let spending_limit = ctx
+                .accounts
+                .spending_limit
+                .as_ref()
+                .ok_or(AlphaError::SpendingLimitAccountMissing)?;
+            required = required.max(spending_limit.elevated_threshold as u64);
+        }
+        require!(
+            owner_signer_weight(&wallet, &ctx.accounts.authority, ctx.remaining_accounts)?
+                >= required,
+            AlphaError::OwnerThresholdNotMet
+        );
+
+        require!(
+            wallet.active_recovery().is_none(),
+            AlphaError::RecoveryInProgress
+        );
+        let timelock = wallet.effective_transfer_timelock(&Clock::get()?);
+        require_below_transfer_timelock(
+            timelock.as_ref(),
+            ctx.accounts.wallet_treasury.to_account_info().lamports(),
+        )?;
+        let destination = ctx.accounts.destination.key();
+        if wallet.address_book_enabled != 0 {
+            let address_book = ctx
+                .accounts
+                .address_book
+                .as_ref()
+                .ok_or(AlphaError::AddressBookAccountMissing)?;
+            require!(
+                address_book.contains(&destination),
+                AlphaError::AddressBookEntryMissing
+            );
+        }
+
+        if wallet.session_registry_enabled != 0 {
+            require!(
+                ctx.accounts.session_registry.is_some(),
+                AlphaError::SessionRegistryAccountMissing
+            );
+        }
+
+        let sweep_count = token_accounts as usize * 2;
+        require!(
+            ctx.remaining_accounts.len() >= sweep_count,
+            AlphaError::InvalidSweepAccount
+        );
+        let (sweep_accounts, session_accounts) = ctx.remaining_accounts.split_at(sweep_count);
+        let mut swept = Vec::with_capacity(token_accounts as usize);
+        for pair in sweep_accounts.chunks(2) {
+            swept.push(sweep_token_account(
+                wallet_key,
+                wallet.treasury_bump,
+                &ctx.accounts.wallet_treasury,
+                &pair[0],
+                &pair[1],
+                &ctx.accounts.destination.to_account_info(),
+                &ctx.accounts.token_program,
+            )?);
+        }
+        require_token_outflows_below_timelock(timelock.as_ref(), &swept)?;
+        let sessions_closed = force_close_sessions(
+            &mut wallet,
+            wallet_key,
+            session_accounts,
+            &ctx.accounts.destination.to_account_info(),
+        )?;
+        require!(wallet.active_sessions == 0, AlphaError::SessionsStillOpen);
+        // Release is permissionless, so a leftover pending transfer or proposal would still bind a
+        // wallet later initialised at the same address.
+        require!(
+            wallet.open_pending_transfers == 0,
+            AlphaError::PendingTransfersStillOpen
+        );
+        require!(wallet.open_proposals == 0, AlphaError::ProposalsStillOpen);
+
+        emit!(WalletClosed {
+            wallet: wallet_key,
+            destination,
+            lamports: ctx.accounts.wallet_treasury.to_account_info().lamports(),
+            token_accounts,
+            sessions_closed,
+        });
+
+        Ok(())
+    }
+
+    pub fn migrate_vault_config(ctx: Context<MigrateAccount>) -> Result<()> {
+        migrate_versioned::<VaultConfig>(
+            &ctx.accounts.target.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            None,
+            &ctx.accounts.system_program,
+            |_| VaultConfig::LEN,
+        )?;
+        Ok(())
+    }
+
+    pub fn migrate_vault_balance(ctx: Context<MigrateAccount>) -> Result<()> {
+        migrate_versioned::<VaultBalance>(
+            &ctx.accounts.target.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            None,
+            &ctx.accounts.system_program,
+            |_| VaultBalance::LEN,
+        )?;
+        Ok(())
+    }
+
+    pub fn migrate_wallet_state(ctx: Context<MigrateWalletState>) -> Result<()> {
+        let target = ctx.accounts.target.to_account_info();
+        let legacy = WalletState::read_legacy(&target)?;
+
+        // The Borsh bytes are cleared and the sections filled in with room to spare, then the
+        // account is trimmed to what the wallet holds.
+        target.realloc(WalletState::MAX_LEN, false)?;
+        let space = {
+            let mut data = target.try_borrow_mut_data()?;
+            data[8..].fill(0);
+            let mut wallet = WalletView::new_mut(&mut data);
+            wallet.upgrade_v0(legacy)?;
+            wallet.current_space()
+        };
+        target.realloc(space, false)?;
+        fund_migration(
+            &target,
+            &ctx.accounts.payer.to_account_info(),
+            Some(&ctx.accounts.wallet_treasury.to_account_info()),
+            &ctx.accounts.system_program,
+            space,
+        )?;
+
+        emit!(AccountMigrated {
+            account: target.key(),
+            from_version: 0,
+            to_version: WalletState::VERSION,
+        });
+        Ok(())
+    }
+
+    pub fn migrate_session_key(ctx: Context<MigrateSessionKey>) -> Result<()> {
+        let wallet_key = ctx.accounts.wallet_state.key();
+        let mut wallet = ctx.accounts.wallet_state.load_wallet_mut()?;
+        let session_epoch = wallet.session_nonce;
+        let session = migrate_versioned::<SessionKeyAccount>(
+            &ctx.accounts.session_account.to_account_info(),
+            &ctx.accounts.payer.to_account_info(),
+            Some(&ctx.accounts.wallet_treasury.to_account_info()),
+            &ctx.accounts.system_program,
+            |session| {
+                // Sessions that predate epochs stay valid until the next `revoke_all_sessions`.
+                session.epoch = session_epoch;
+                SessionKeyAccount::space(MAX_SESSION_PROGRAMS, MAX_SESSION_TOKEN_ALLOWANCES)
+            },
+        )?;
+
+        require_keys_eq!(
+            session.wallet,
+            wallet_key,
+            AlphaError::SessionKeyWalletMismatch
+        );
+        // v0 wallets did not count their sessions, so each one is counted as it migrates.
+        wallet.active_sessions = wallet
+            .active_sessions
+            .checked_add(1)
+            .expect("active session overflow");
+        if let Some(registry) = session_registry(&wallet, ctx.accounts.session_registry.as_mut())? {
+            registry.upsert(&session)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = VaultConfig::LEN,
+        seeds = [b"vault", admin.key().as_ref()],
+        bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+    #[account(
+        init,
+        payer = admin,
         space = VaultTreasury::LEN,
         seeds = [b"vault-treasury", vault_config.key().as_ref()],
         bump
     )]
-    pub vault_treasury: Account<'info, VaultTreasury>,
+    pub vault_treasury: Account<'info, VaultTreasury>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetVaultOperator<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault_config: Account<'info, VaultConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_config.admin.as_ref()],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+    #[account(
+        mut,
+        seeds = [b"vault-treasury", vault_config.key().as_ref()],
+        bump = vault_treasury.bump
+    )]
+    pub vault_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = VaultBalance::LEN,
+        seeds = [b"vault-balance", vault_config.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub vault_balance: Account<'info, VaultBalance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_config.admin.as_ref()],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+    #[account(
+        mut,
+        seeds = [b"vault-treasury", vault_config.key().as_ref()],
+        bump = vault_treasury.bump
+    )]
+    pub vault_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        seeds = [b"vault-balance", vault_config.key().as_ref(), vault_balance.owner.as_ref()],
+        bump = vault_balance.bump
+    )]
+    pub vault_balance: Account<'info, VaultBalance>,
+    /// CHECK: lamports destination validated at runtime
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    owners: Vec<OwnerShare>,
+    threshold: u16,
+    guardians: Vec<Pubkey>,
+    guardian_quorum: u8,
+    guardian_cooldown: u64,
+    guardian_cooldown_unit: WindowUnit,
+    operator_delegate: Option<Pubkey>
+)]
+pub struct InitWallet<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = WalletState::initial_space(
+            owners.len(),
+            guardians.len(),
+            operator_delegate.iter().count()
+        )
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init,
+        payer = payer,
+        space = VaultTreasury::LEN,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    salt: [u8; 32],
+    owners: Vec<OwnerShare>,
+    threshold: u16,
+    guardians: Vec<Pubkey>,
+    guardian_quorum: u8,
+    guardian_cooldown: u64,
+    guardian_cooldown_unit: WindowUnit,
+    operator_delegate: Option<Pubkey>
+)]
+pub struct InitDerivedWallet<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    // The creator signs so nobody can front-run the derived address with other owners.
+    pub creator: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = WalletState::initial_space(
+            owners.len(),
+            guardians.len(),
+            operator_delegate.iter().count()
+        ),
+        seeds = [b"wallet", creator.key().as_ref(), salt.as_ref()],
+        bump
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init,
+        payer = payer,
+        space = VaultTreasury::LEN,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WalletOwnerAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct WalletTransferOwner<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    /// CHECK: destination account validated on transfer
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Option<Account<'info, SpendingLimit>>,
+    #[account(
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Option<Account<'info, AddressBook>>,
+    /// CHECK: derived from the wallet pending transfer index and created in instruction
+    #[account(mut)]
+    pub pending_transfer: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpendingLimit<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SpendingLimit::LEN,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub spending_limit: Account<'info, SpendingLimit>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveSpendingLimit<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Account<'info, SpendingLimit>,
+}
+
+#[derive(Accounts)]
+pub struct SetAddressBook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = AddressBook::LEN,
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub address_book: Account<'info, AddressBook>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAddressBook<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Account<'info, AddressBook>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAddressBook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Account<'info, AddressBook>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSessionKey<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    /// CHECK: created in instruction with rent from the treasury
+    #[account(
+        mut,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_authority.key().as_ref()],
+        bump
+    )]
+    pub session_account: UncheckedAccount<'info>,
+    /// CHECK: derived in instruction
+    pub session_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitSessionRegistry<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init,
+        payer = authority,
+        space = SessionRegistry::LEN,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub session_registry: Account<'info, SessionRegistry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestSession<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub session_authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init,
+        payer = payer,
+        space = SessionRequest::space(),
+        seeds = [b"session-request", wallet_state.key().as_ref(), session_authority.key().as_ref()],
+        bump
+    )]
+    pub session_request: Account<'info, SessionRequest>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveSessionRequest<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [b"session-request", wallet_state.key().as_ref(), session_request.authority.as_ref()],
+        bump = session_request.bump
+    )]
+    pub session_request: Account<'info, SessionRequest>,
+    /// CHECK: rent refund destination matched against session_request.payer
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+    /// CHECK: created in instruction with rent from the treasury
+    #[account(
+        mut,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_request.authority.as_ref()],
+        bump
+    )]
+    pub session_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RejectSessionRequest<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        seeds = [b"session-request", wallet_state.key().as_ref(), session_request.authority.as_ref()],
+        bump = session_request.bump
+    )]
+    pub session_request: Account<'info, SessionRequest>,
+    /// CHECK: rent refund destination matched against session_request.payer
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = wallet_treasury,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_account.authority.as_ref()],
+        bump = session_account.bump
+    )]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredSession<'info> {
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = wallet_treasury,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_account.authority.as_ref()],
+        bump = session_account.bump
+    )]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSessionKey<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_account.authority.as_ref()],
+        bump = session_account.bump
+    )]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+}
+
+#[derive(Accounts)]
+pub struct RotateSessionAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = wallet_treasury,
+        seeds = [b"session-key", wallet_state.key().as_ref(), session_account.authority.as_ref()],
+        bump = session_account.bump
+    )]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    /// CHECK: created in instruction with rent from the treasury
+    #[account(
+        mut,
+        seeds = [b"session-key", wallet_state.key().as_ref(), new_session_authority.key().as_ref()],
+        bump
+    )]
+    pub new_session_account: UncheckedAccount<'info>,
+    /// CHECK: only seeds the new session account
+    pub new_session_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SessionTransfer<'info> {
+    #[account(mut)]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    #[account(mut)]
+    pub session_authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = wallet_state.key() == session_account.wallet,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    /// CHECK: destination validated on transfer
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Option<Account<'info, SpendingLimit>>,
+    #[account(
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Option<Account<'info, AddressBook>>,
+    /// CHECK: derived from the wallet pending transfer index and created in instruction
+    #[account(mut)]
+    pub pending_transfer: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SessionExecute<'info> {
+    #[account(mut)]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    pub session_authority: Signer<'info>,
+    #[account(
+        constraint = wallet_state.key() == session_account.wallet,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct OperatorTransfer<'info> {
+    #[account(mut)]
+    pub operator: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    /// CHECK: destination validated on transfer
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Option<Account<'info, SpendingLimit>>,
+    #[account(
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Option<Account<'info, AddressBook>>,
+    /// CHECK: derived from the wallet pending transfer index and created in instruction
+    #[account(mut)]
+    pub pending_transfer: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleasePendingTransfer<'info> {
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        has_one = destination,
+        constraint = pending_transfer.wallet == wallet_state.key() @ AlphaError::PendingTransferWalletMismatch,
+        seeds = [b"pending-transfer", wallet_state.key().as_ref(), &pending_transfer.index.to_le_bytes()],
+        bump = pending_transfer.bump
+    )]
+    pub pending_transfer: Account<'info, PendingTransfer>,
+    /// CHECK: matched against pending_transfer.destination
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: rent refund destination matched against pending_transfer.payer
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPendingTransfer<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        constraint = pending_transfer.wallet == wallet_state.key() @ AlphaError::PendingTransferWalletMismatch,
+        seeds = [b"pending-transfer", wallet_state.key().as_ref(), &pending_transfer.index.to_le_bytes()],
+        bump = pending_transfer.bump
+    )]
+    pub pending_transfer: Account<'info, PendingTransfer>,
+    /// CHECK: rent refund destination matched against pending_transfer.payer
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTreasuryTokenAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = wallet_treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTreasuryTokenAccount<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet_treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+    /// CHECK: receives the reclaimed rent
+    #[account(mut)]
+    pub destination: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WalletTokenTransferOwner<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet_treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SessionTokenTransfer<'info> {
+    #[account(mut)]
+    pub session_account: Account<'info, SessionKeyAccount>,
+    pub session_authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = wallet_state.key() == session_account.wallet,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet_treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct OperatorTokenTransfer<'info> {
+    pub operator: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet_treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub destination: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteInstructions<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianAction<'info> {
+    pub guardian: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        init,
+        payer = proposer,
+        space = Proposal::space(
+            wallet_state.load()?.capacity.max_owners as usize,
+            MAX_SESSION_PROGRAMS
+        ),
+        seeds = [
+            b"proposal",
+            wallet_state.key().as_ref(),
+            &wallet_state.load()?.proposal_index.to_le_bytes()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposalVote<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        constraint = proposal.wallet == wallet_state.key() @ AlphaError::ProposalWalletMismatch,
+        seeds = [b"proposal", wallet_state.key().as_ref(), &proposal.index.to_le_bytes()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(mut)]
+    pub executor: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        constraint = proposal.wallet == wallet_state.key() @ AlphaError::ProposalWalletMismatch,
+        seeds = [b"proposal", wallet_state.key().as_ref(), &proposal.index.to_le_bytes()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    /// CHECK: matched against the proposal transfer destination
+    #[account(mut)]
+    pub destination: Option<UncheckedAccount<'info>>,
+    /// CHECK: derived from the proposal session authority and created in instruction
+    #[account(mut)]
+    pub session_account: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Option<Account<'info, SpendingLimit>>,
+    #[account(
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Option<Account<'info, AddressBook>>,
+    /// CHECK: derived from the wallet pending transfer index and created in instruction
+    #[account(mut)]
+    pub pending_transfer: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseProposal<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        constraint = proposal.wallet == wallet_state.key() @ AlphaError::ProposalWalletMismatch,
+        seeds = [b"proposal", wallet_state.key().as_ref(), &proposal.index.to_le_bytes()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    /// CHECK: rent refund destination matched against proposal.proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseWallet<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = destination,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    #[account(
+        mut,
+        close = destination,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump = wallet_state.load()?.treasury_bump
+    )]
+    pub wallet_treasury: Account<'info, VaultTreasury>,
+    #[account(
+        mut,
+        close = destination,
+        seeds = [b"spending-limit", wallet_state.key().as_ref()],
+        bump = spending_limit.bump
+    )]
+    pub spending_limit: Option<Account<'info, SpendingLimit>>,
+    #[account(
+        mut,
+        close = destination,
+        seeds = [b"address-book", wallet_state.key().as_ref()],
+        bump = address_book.bump
+    )]
+    pub address_book: Option<Account<'info, AddressBook>>,
+    #[account(
+        mut,
+        close = destination,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
+    /// CHECK: receives the swept lamports and all reclaimed rent
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: discriminator and layout version are checked during migration
+    #[account(mut, owner = crate::ID)]
+    pub target: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateWalletState<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: discriminator and layout version are checked during migration
+    #[account(mut, owner = crate::ID)]
+    pub target: UncheckedAccount<'info>,
+    /// CHECK: receives any rent above the new size; only the address is checked
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", target.key().as_ref()],
+        bump
+    )]
+    pub wallet_treasury: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateSessionKey<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = WalletState::is_current(wallet_state.as_ref()) @ AlphaError::UnsupportedAccountVersion
+    )]
+    pub wallet_state: AccountLoader<'info, WalletState>,
+    /// CHECK: discriminator and layout version are checked during migration
+    #[account(mut, owner = crate::ID)]
+    pub session_account: UncheckedAccount<'info>,
+    /// CHECK: receives any rent above the new size; only the address is checked
+    #[account(
+        mut,
+        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
+        bump
+    )]
+    pub wallet_treasury: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"session-registry", wallet_state.key().as_ref()],
+        bump = session_registry.bump
+    )]
+    pub session_registry: Option<Account<'info, SessionRegistry>>,
     pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct SetVaultOperator<'info> {
-    #[account(mut, has_one = admin)]
-    pub vault_config: Account<'info, VaultConfig>,
-    pub admin: Signer<'info>,
+#[event]
+pub struct VaultDepositEvent {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance_after: u64,
+}
+
+#[event]
+pub struct VaultWithdrawalEvent {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub balance_after: u64,
+}
+
+#[event]
+pub struct WalletTransferEvent {
+    pub wallet: Pubkey,
+    pub actor: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub memo: Option<Vec<u8>>,
+    pub via_session: bool,
+}
+
+#[event]
+pub struct WalletTokenTransferEvent {
+    pub wallet: Pubkey,
+    pub actor: Pubkey,
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<Vec<u8>>,
+    pub via_session: bool,
+}
+
+#[event]
+pub struct WalletInstructionsExecuted {
+    pub wallet: Pubkey,
+    pub actor: Pubkey,
+    pub programs: Vec<Pubkey>,
+    pub via_session: bool,
+}
+
+#[event]
+pub struct SpendingLimitUpdated {
+    pub wallet: Pubkey,
+    pub max_outflow: u64,
+    pub window_length: u64,
+    pub window_unit: WindowUnit,
+    pub elevated_threshold: u16,
+}
+
+#[event]
+pub struct SpendingLimitRemoved {
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct WalletOperatorUpdated {
+    pub wallet: Pubkey,
+    pub operator: Pubkey,
+    pub config: Option<OperatorConfig>,
+}
+
+#[event]
+pub struct ActionThresholdUpdated {
+    pub wallet: Pubkey,
+    pub action: ActionClass,
+    pub threshold: Option<u16>,
+}
+
+#[event]
+pub struct OwnerRolesUpdated {
+    pub wallet: Pubkey,
+    pub owner: Pubkey,
+    pub roles: u8,
+}
+
+#[event]
+pub struct WalletCapacityUpdated {
+    pub wallet: Pubkey,
+    pub capacity: WalletCapacity,
+}
+
+#[event]
+pub struct OwnerQuotaUpdated {
+    pub wallet: Pubkey,
+    pub owner: Pubkey,
+    pub quota: Option<OwnerQuotaConfig>,
+}
+
+#[event]
+pub struct AddressBookPolicyUpdated {
+    pub wallet: Pubkey,
+    pub min_delayed_amount: u64,
+    pub delay_slots: u64,
+}
+
+#[event]
+pub struct AddressBookEntryUpdated {
+    pub wallet: Pubkey,
+    pub address: Pubkey,
+    pub added: bool,
+}
+
+#[event]
+pub struct AddressBookRemoved {
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct PendingTransferQueued {
+    pub wallet: Pubkey,
+    pub pending_transfer: Pubkey,
+    pub index: u64,
+    pub requested_by: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub reason: PendingTransferReason,
+    pub release_slot: u64,
+}
+
+#[event]
+pub struct PendingTransferReleased {
+    pub wallet: Pubkey,
+    pub pending_transfer: Pubkey,
+    pub index: u64,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub reason: PendingTransferReason,
+}
+
+#[event]
+pub struct PendingTransferCancelled {
+    pub wallet: Pubkey,
+    pub pending_transfer: Pubkey,
+    pub index: u64,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub reason: PendingTransferReason,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct TransferTimelockUpdated {
+    pub wallet: Pubkey,
+    pub timelock: Option<TransferTimelock>,
+    pub effective_slot: u64,
+}
+
+#[event]
+pub struct TransferTimelockUpdateCancelled {
+    pub wallet: Pubkey,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct SessionKeyRegistered {
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    pub expires_at_slot: Option<u64>,
+    pub expires_at_timestamp: Option<i64>,
+    pub usage_limit: Option<u64>,
+}
+
+#[event]
+pub struct SessionRegistryInitialized {
+    pub wallet: Pubkey,
+    pub sessions: u32,
+}
+
+#[event]
+pub struct SessionRequested {
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    pub session_request: Pubkey,
+}
+
+#[event]
+pub struct SessionRequestResolved {
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    pub resolved_by: Pubkey,
+    pub approved: bool,
+    pub amended: bool,
+}
+
+#[event]
+pub struct SessionKeyRevoked {
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct AllSessionsRevoked {
+    pub wallet: Pubkey,
+    pub session_epoch: u64,
+}
+
+#[event]
+pub struct SessionKeyExtended {
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    pub expires_at_slot: Option<u64>,
+    pub expires_at_timestamp: Option<i64>,
+    pub usage_limit: Option<u64>,
+}
+
+#[event]
+pub struct SessionAuthorityRotated {
+    pub wallet: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct DelegatesFrozen {
+    pub wallet: Pubkey,
+    pub frozen_by: Pubkey,
+}
+
+#[event]
+pub struct DelegatesUnfrozen {
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct RecoveryProposed {
+    pub wallet: Pubkey,
+    pub guardian: Pubkey,
+    pub execute_after_slot: Option<u64>,
+    pub execute_after_timestamp: Option<i64>,
+}
+
+#[event]
+pub struct RecoveryVote {
+    pub wallet: Pubkey,
+    pub guardian: Pubkey,
+    pub approvals: u8,
+}
+
+#[event]
+pub struct RecoveryCompleted {
+    pub wallet: Pubkey,
+    pub executed_slot: u64,
+    pub executed_timestamp: i64,
+}
+
+#[event]
+pub struct WalletClosed {
+    pub wallet: Pubkey,
+    pub destination: Pubkey,
+    pub lamports: u64,
+    pub token_accounts: u8,
+    pub sessions_closed: u32,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct ProposalCreated {
+    pub wallet: Pubkey,
+    pub proposal: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub expires_at_slot: u64,
+}
+
+#[event]
+pub struct ProposalVoted {
+    pub wallet: Pubkey,
+    pub proposal: Pubkey,
+    pub owner: Pubkey,
+    pub approved: bool,
+    pub approval_weight: u64,
+    pub rejection_weight: u64,
+}
+
+#[event]
+pub struct ProposalExecuted {
+    pub wallet: Pubkey,
+    pub proposal: Pubkey,
+    pub index: u64,
+    pub executor: Pubkey,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn initialize_wallet(
+    wallet_state: &AccountLoader<'_, WalletState>,
+    wallet_treasury: &mut Account<'_, VaultTreasury>,
+    treasury_bump: u8,
+    owners: Vec<OwnerShare>,
+    threshold: u16,
+    guardians: Vec<Pubkey>,
+    guardian_quorum: u8,
+    guardian_cooldown: u64,
+    guardian_cooldown_unit: WindowUnit,
+    operator_delegate: Option<Pubkey>,
+    capacity: Option<WalletCapacity>,
+) -> Result<()> {
+    let capacity = capacity.unwrap_or_default();
+    validate_wallet_capacity(&capacity)?;
+    validate_owner_set(&owners, capacity.max_owners)?;
+    validate_guardians(&guardians, guardian_quorum, capacity.max_guardians)?;
+
+    let total_weight: u64 = owners.iter().map(|o| o.weight as u64).sum();
+    require!(!owners.is_empty(), AlphaError::OwnerThresholdNotMet);
+    require!(
+        (threshold as u64) > 0 && (threshold as u64) <= total_weight,
+        AlphaError::OwnerThresholdNotMet
+    );
+
+    // The account starts zeroed, so only the configured fields are written.
+    let mut wallet = wallet_state.load_wallet_init()?;
+    wallet.version = WalletState::VERSION;
+    wallet.treasury_bump = treasury_bump;
+    wallet.replace_owners(&owners, threshold);
+    wallet.set_guardians(
+        &guardians,
+        guardian_quorum,
+        guardian_cooldown,
+        guardian_cooldown_unit,
+    );
+    if let Some(operator) = operator_delegate {
+        wallet.push_operator(OperatorEntry::unrestricted(operator))?;
+    }
+    wallet.capacity = capacity;
+
+    wallet_treasury.vault = wallet_state.key();
+    wallet_treasury.bump = treasury_bump;
+
+    Ok(())
+}
+
+// Capacity narrows the program-wide bounds rather than extending them: a recovery carries the
+// replacement owners and the guardian approvals in fixed `MAX_OWNERS` / `MAX_GUARDIAN_VOTES`
+// arrays, so a wallet above them could never be recovered in full.
+fn validate_wallet_capacity(capacity: &WalletCapacity) -> Result<()> {
+    require!(
+        capacity.max_owners > 0
+            && capacity.max_owners as usize <= MAX_OWNERS
+            && capacity.max_guardians as usize <= MAX_GUARDIANS,
+        AlphaError::InvalidWalletCapacity
+    );
+    Ok(())
+}
+
+fn validate_owner_set(owners: &[OwnerShare], max_owners: u8) -> Result<()> {
+    require!(!owners.is_empty(), AlphaError::OwnerThresholdNotMet);
+    require!(
+        owners.len() <= max_owners as usize,
+        AlphaError::TooManyOwners
+    );
+
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(owners.len());
+    for owner in owners {
+        require!(owner.weight > 0, AlphaError::OwnerThresholdNotMet);
+        if seen.contains(&owner.owner) {
+            return err!(AlphaError::DuplicateOwner);
+        }
+        seen.push(owner.owner);
+    }
+    Ok(())
+}
+
+fn validate_threshold(owners: &[OwnerShare], threshold: u16) -> Result<()> {
+    let total_weight: u64 = owners.iter().map(|o| o.weight as u64).sum();
+    require!(
+        (threshold as u64) > 0 && (threshold as u64) <= total_weight,
+        AlphaError::OwnerThresholdNotMet
+    );
+    Ok(())
+}
+
+fn validate_session_config(config: &SessionConfig) -> Result<()> {
+    require!(
+        config.allowed_programs.len() <= MAX_SESSION_PROGRAMS,
+        AlphaError::SessionProgramNotAuthorised
+    );
+    require!(
+        config.token_allowances.len() <= MAX_SESSION_TOKEN_ALLOWANCES,
+        AlphaError::InvalidTokenAllowances
+    );
+
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(config.token_allowances.len());
+    for allowance in &config.token_allowances {
+        if seen.contains(&allowance.mint) {
+            return err!(AlphaError::InvalidTokenAllowances);
+        }
+        seen.push(allowance.mint);
+    }
+
+    require!(
+        config.allowed_destinations.len() <= MAX_SESSION_DESTINATIONS,
+        AlphaError::InvalidSessionDestinations
+    );
+    require!(
+        config.instruction_templates.len() <= MAX_SESSION_TEMPLATES,
+        AlphaError::InvalidInstructionTemplate
+    );
+    for template in &config.instruction_templates {
+        // Templates narrow `allowed_programs`; they cannot grant a program on their own.
+        require!(
+            config.allowed_programs.contains(&template.program_id)
+                && !template.discriminator.is_empty()
+                && template.discriminator.len() <= MAX_TEMPLATE_DISCRIMINATOR_LENGTH
+                && template.fixed_accounts.len() <= MAX_TEMPLATE_FIXED_ACCOUNTS
+                && template
+                    .fixed_accounts
+                    .iter()
+                    .all(|fixed| (fixed.position as usize) < MAX_INSTRUCTION_ACCOUNTS),
+            AlphaError::InvalidInstructionTemplate
+        );
+    }
+
+    if let Some(rate_limit) = config.rate_limit {
+        require!(
+            rate_limit.period_length > 0
+                && (rate_limit.calls_per_period.is_some() || rate_limit.value_per_period.is_some()),
+            AlphaError::InvalidSessionRateLimit
+        );
+    }
+    Ok(())
+}
+
+fn validate_wallet_instructions(instructions: &[WalletInstruction]) -> Result<()> {
+    require!(
+        !instructions.is_empty() && instructions.len() <= MAX_WALLET_INSTRUCTIONS,
+        AlphaError::InvalidWalletInstruction
+    );
+    for instruction in instructions {
+        require!(
+            instruction.account_indexes.len() <= MAX_INSTRUCTION_ACCOUNTS
+                && instruction.data.len() <= MAX_INSTRUCTION_DATA_LENGTH,
+            AlphaError::InvalidWalletInstruction
+        );
+        require!(
+            instruction.program_id != crate::ID,
+            AlphaError::WalletInstructionNotAllowed
+        );
+    }
+    Ok(())
+}
+
+fn validate_operator_config(config: &OperatorConfig) -> Result<()> {
+    require!(
+        config.scopes != 0 && config.scopes & !OPERATOR_SCOPE_ALL == 0,
+        AlphaError::InvalidOperatorConfig
+    );
+    if let Some(ref policy) = config.policy {
+        require!(
+            config.scopes & OPERATOR_SCOPE_TOKEN_TRANSFER == 0
+                && policy.period_length > 0
+                && policy.max_single_transfer <= policy.period_allowance
+                && policy.allowed_destinations.len() <= MAX_OPERATOR_DESTINATIONS,
+            AlphaError::InvalidOperatorConfig
+        );
+    }
+    Ok(())
+}
+
+fn validate_proposal_action(action: &ProposalAction, max_owners: u8) -> Result<()> {
+    match action {
+        ProposalAction::Transfer { amount, memo, .. } => {
+            require!(*amount > 0, AlphaError::AmountMustBePositive);
+            if let Some(memo_bytes) = memo {
+                require!(memo_bytes.len() <= MAX_MEMO_LENGTH, AlphaError::MemoTooLong);
+            }
+        }
+        ProposalAction::ConfigureOperator { config, .. } => {
+            if let Some(config) = config {
+                validate_operator_config(config)?;
+            }
+        }
+        ProposalAction::UpdateOwners { owners, threshold } => {
+            validate_owner_set(owners, max_owners)?;
+            validate_threshold(owners, *threshold)?;
+        }
+        ProposalAction::RegisterSession { config, .. } => validate_session_config(config)?,
+        ProposalAction::ExecuteInstructions { instructions } => {
+            validate_wallet_instructions(instructions)?
+        }
+    }
+    Ok(())
+}
+
+fn validate_guardians(guardians: &[Pubkey], quorum: u8, max_guardians: u8) -> Result<()> {
+    require!(
+        guardians.len() <= max_guardians as usize,
+        AlphaError::TooManyGuardians
+    );
+    if guardians.is_empty() {
+        require!(quorum == 0, AlphaError::GuardianQuorumNotMet);
+        return Ok(());
+    }
+    require!(
+        quorum > 0 && quorum as usize <= guardians.len(),
+        AlphaError::GuardianQuorumNotMet
+    );
+
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(guardians.len());
+    for guardian in guardians {
+        if seen.contains(guardian) {
+            return err!(AlphaError::DuplicateGuardian);
+        }
+        seen.push(*guardian);
+    }
+    Ok(())
+}
+
+fn verify_owner_threshold<'info>(
+    wallet: &impl WalletAccount,
+    action: ActionClass,
+    primary: &Signer<'info>,
+    remaining: &[AccountInfo<'info>],
+) -> Result<()> {
+    let total_weight = owner_signer_weight(wallet, primary, remaining)?;
+    require!(
+        total_weight >= wallet.threshold_for(action) as u64,
+        AlphaError::OwnerThresholdNotMet
+    );
+    Ok(())
+}
+
+fn ensure_owner_role(wallet: &impl WalletAccount, owner: &Pubkey, role: u8) -> Result<()> {
+    require!(
+        wallet.owner_weight(owner).is_some(),
+        AlphaError::NotWalletOwner
+    );
+    require!(
+        wallet.owner_has_role(owner, role),
+        AlphaError::OwnerRoleMissing
+    );
+    Ok(())
+}
+
+fn owner_signer_weight<'info>(
+    wallet: &impl WalletAccount,
+    primary: &Signer<'info>,
+    remaining: &[AccountInfo<'info>],
+) -> Result<u64> {
+    let mut total_weight: u64 = 0;
+    let mut seen: Vec<Pubkey> = Vec::new();
+
+    accumulate_owner_weight(
+        wallet,
+        &mut total_weight,
+        &mut seen,
+        &primary.to_account_info(),
+    )?;
+    for account in remaining {
+        accumulate_owner_weight(wallet, &mut total_weight, &mut seen, account)?;
+    }
+
+    Ok(total_weight)
+}
+
+fn verify_spending_limit_authority<'info>(
+    wallet: &impl WalletAccount,
+    spending_limit: &SpendingLimit,
+    primary: &Signer<'info>,
+    remaining: &[AccountInfo<'info>],
+) -> Result<()> {
+    // Loosening an active limit needs the same weight as spending past it.
+    let mut required = wallet.threshold_for(ActionClass::UpdatePolicy) as u64;
+    if wallet.spending_limit_enabled != 0 {
+        required = required.max(spending_limit.elevated_threshold as u64);
+    }
+    require!(
+        owner_signer_weight(wallet, primary, remaining)? >= required,
+        AlphaError::OwnerThresholdNotMet
+    );
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"vault", vault_config.admin.as_ref()],
-        bump = vault_config.bump
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
-    #[account(
-        mut,
-        seeds = [b"vault-treasury", vault_config.key().as_ref()],
-        bump = vault_treasury.bump
-    )]
-    pub vault_treasury: Account<'info, VaultTreasury>,
-    #[account(
-        init_if_needed,
-        payer = owner,
-        space = VaultBalance::LEN,
-        seeds = [b"vault-balance", vault_config.key().as_ref(), owner.key().as_ref()],
-        bump
-    )]
-    pub vault_balance: Account<'info, VaultBalance>,
-    pub system_program: Program<'info, System>,
+fn enforce_spending_limit(
+    wallet: &impl WalletAccount,
+    spending_limit: Option<&mut Account<'_, SpendingLimit>>,
+    amount: u64,
+    owner_weight: Option<u64>,
+) -> Result<()> {
+    if wallet.spending_limit_enabled == 0 {
+        return Ok(());
+    }
+    let spending_limit = spending_limit.ok_or(AlphaError::SpendingLimitAccountMissing)?;
+
+    let clock = Clock::get()?;
+    spending_limit.roll_window(&clock);
+    if spending_limit.exceeds(amount) {
+        // `owner_weight` is the signer or proposal approval weight; delegates never pass one.
+        require!(
+            owner_weight
+                .map(|weight| weight >= spending_limit.elevated_threshold as u64)
+                .unwrap_or(false),
+            AlphaError::SpendingLimitExceeded
+        );
+    }
+    spending_limit.spent_in_window = spending_limit.spent_in_window.saturating_add(amount);
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"vault", vault_config.admin.as_ref()],
-        bump = vault_config.bump
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
-    #[account(
-        mut,
-        seeds = [b"vault-treasury", vault_config.key().as_ref()],
-        bump = vault_treasury.bump
-    )]
-    pub vault_treasury: Account<'info, VaultTreasury>,
-    #[account(
-        mut,
-        seeds = [b"vault-balance", vault_config.key().as_ref(), vault_balance.owner.as_ref()],
-        bump = vault_balance.bump
-    )]
-    pub vault_balance: Account<'info, VaultBalance>,
-    /// CHECK: lamports destination validated at runtime
-    #[account(mut)]
-    pub recipient: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
+// The registry to keep in step with session changes, once the wallet has one.
+fn session_registry<'a, 'info>(
+    wallet: &impl WalletAccount,
+    registry: Option<&'a mut Account<'info, SessionRegistry>>,
+) -> Result<Option<&'a mut Account<'info, SessionRegistry>>> {
+    if wallet.session_registry_enabled == 0 {
+        return Ok(None);
+    }
+    registry
+        .map(Some)
+        .ok_or(error!(AlphaError::SessionRegistryAccountMissing))
 }
 
-#[derive(Accounts)]
-pub struct InitWallet<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    #[account(
-        init,
-        payer = payer,
-        space = WalletState::space(MAX_OWNERS, MAX_GUARDIANS)
-    )]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        init,
-        payer = payer,
-        space = VaultTreasury::LEN,
-        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
-        bump
-    )]
-    pub wallet_treasury: Account<'info, VaultTreasury>,
-    pub system_program: Program<'info, System>,
+fn destination_delay(
+    wallet: &impl WalletAccount,
+    address_book: Option<&Account<'_, AddressBook>>,
+    destination: &Pubkey,
+    amount: u64,
+) -> Result<Option<(PendingTransferReason, u64)>> {
+    if wallet.address_book_enabled == 0 {
+        return Ok(None);
+    }
+    let address_book = address_book.ok_or(AlphaError::AddressBookAccountMissing)?;
+
+    Ok(address_book.requires_delay(destination, amount).then_some((
+        PendingTransferReason::UnknownDestination,
+        address_book.delay_slots,
+    )))
 }
 
-#[derive(Accounts)]
-pub struct WalletOwnerAuthority<'info> {
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
+fn transfer_delay(
+    wallet: &impl WalletAccount,
+    address_book: Option<&Account<'_, AddressBook>>,
+    destination: &Pubkey,
+    amount: u64,
+) -> Result<Option<(PendingTransferReason, u64)>> {
+    let timelock = wallet
+        .effective_transfer_timelock(&Clock::get()?)
+        .filter(|timelock| amount >= timelock.min_amount)
+        .map(|timelock| (PendingTransferReason::HighValue, timelock.delay_slots));
+
+    Ok(
+        destination_delay(wallet, address_book, destination, amount)?
+            .into_iter()
+            .chain(timelock)
+            .max_by_key(|(_, delay_slots)| *delay_slots),
+    )
 }
 
-#[derive(Accounts)]
-pub struct WalletTransferOwner<'info> {
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        mut,
-        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
-        bump = wallet_state.treasury_bump
-    )]
-    pub wallet_treasury: Account<'info, VaultTreasury>,
-    /// CHECK: destination account validated on transfer
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
+// Outflows that cannot be queued as a lamport pending transfer (token amounts, invoked instructions,
+// a wallet sweep) are held to `min_amount` in their own base units. At or above it they are refused
+// and have to wait until the timelock is removed, which itself takes one current delay.
+fn require_below_transfer_timelock(timelock: Option<&TransferTimelock>, amount: u64) -> Result<()> {
+    if let Some(timelock) = timelock {
+        require!(
+            amount < timelock.min_amount,
+            AlphaError::TransferTimelockActive
+        );
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct RegisterSessionKey<'info> {
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        init,
-        payer = payer,
-        space = SessionKeyAccount::space(MAX_SESSION_PROGRAMS),
-        seeds = [b"session-key", wallet_state.key().as_ref(), session_authority.key().as_ref()],
-        bump
-    )]
-    pub session_account: Account<'info, SessionKeyAccount>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    /// CHECK: derived in instruction
-    pub session_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
+// Outflows of the same mint are summed, so splitting a move across accounts or instructions does not
+// slip under the timelock.
+fn require_token_outflows_below_timelock(
+    timelock: Option<&TransferTimelock>,
+    outflows: &[(Pubkey, u64)],
+) -> Result<()> {
+    let mut by_mint: Vec<(Pubkey, u64)> = Vec::new();
+    for (mint, amount) in outflows {
+        match by_mint.iter_mut().find(|(seen, _)| seen == mint) {
+            Some((_, total)) => *total = total.saturating_add(*amount),
+            None => by_mint.push((*mint, *amount)),
+        }
+    }
+    for (_, total) in by_mint {
+        require_below_transfer_timelock(timelock, total)?;
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct RevokeSessionKey<'info> {
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        mut,
-        close = authority,
-        seeds = [b"session-key", wallet_state.key().as_ref(), session_account.authority.as_ref()],
-        bump = session_account.bump
-    )]
-    pub session_account: Account<'info, SessionKeyAccount>,
+fn queue_pending_transfer<'info>(
+    wallet: &mut impl WalletAccountMut,
+    payer: &AccountInfo<'info>,
+    pending_account: Option<&UncheckedAccount<'info>>,
+    system_program: &Program<'info, System>,
+    transfer: WalletTransferEvent,
+    reason: PendingTransferReason,
+    delay_slots: u64,
+) -> Result<()> {
+    let pending_info = pending_account
+        .ok_or(AlphaError::PendingTransferAccountMismatch)?
+        .to_account_info();
+    let wallet_key = transfer.wallet;
+    let index = wallet.pending_transfer_index;
+    let index_bytes = index.to_le_bytes();
+    let (expected, bump) = Pubkey::find_program_address(
+        &[b"pending-transfer", wallet_key.as_ref(), &index_bytes],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        pending_info.key(),
+        expected,
+        AlphaError::PendingTransferAccountMismatch
+    );
+
+    let clock = Clock::get()?;
+    let pending = PendingTransfer {
+        version: PendingTransfer::VERSION,
+        wallet: wallet_key,
+        index,
+        requested_by: transfer.actor,
+        payer: payer.key(),
+        destination: transfer.destination,
+        amount: transfer.amount,
+        memo: transfer.memo,
+        via_session: transfer.via_session,
+        reason,
+        created_slot: clock.slot,
+        release_slot: clock.slot.saturating_add(delay_slots),
+        bump,
+    };
+    create_program_account(
+        payer,
+        &pending_info,
+        system_program,
+        &[
+            b"pending-transfer",
+            wallet_key.as_ref(),
+            &index_bytes,
+            &[bump],
+        ],
+        PendingTransfer::LEN,
+        &pending,
+    )?;
+
+    wallet.pending_transfer_index = index
+        .checked_add(1)
+        .expect("pending transfer index overflow");
+    wallet.open_pending_transfers = wallet
+        .open_pending_transfers
+        .checked_add(1)
+        .expect("open pending transfer overflow");
+
+    emit!(PendingTransferQueued {
+        wallet: wallet_key,
+        pending_transfer: expected,
+        index,
+        requested_by: pending.requested_by,
+        destination: pending.destination,
+        amount: pending.amount,
+        reason,
+        release_slot: pending.release_slot,
+    });
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct SessionTransfer<'info> {
-    #[account(mut)]
-    pub session_account: Account<'info, SessionKeyAccount>,
-    pub session_authority: Signer<'info>,
-    #[account(
-        mut,
-        constraint = wallet_state.key() == session_account.wallet
-    )]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        mut,
-        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
-        bump = wallet_state.treasury_bump
-    )]
-    pub wallet_treasury: Account<'info, VaultTreasury>,
-    /// CHECK: destination validated on transfer
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
+fn accumulate_owner_weight<'info>(
+    wallet: &impl WalletAccount,
+    total_weight: &mut u64,
+    seen: &mut Vec<Pubkey>,
+    account: &AccountInfo<'info>,
+) -> Result<()> {
+    if !account.is_signer {
+        return Ok(());
+    }
+
+    if seen.contains(account.key) {
+        return Ok(());
+    }
+
+    if let Some(weight) = wallet.owner_weight(account.key) {
+        *total_weight = total_weight
+            .checked_add(weight as u64)
+            .expect("weight overflow");
+        seen.push(*account.key);
+    }
+
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct OperatorTransfer<'info> {
-    pub operator: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
-    #[account(
-        mut,
-        seeds = [b"wallet-treasury", wallet_state.key().as_ref()],
-        bump = wallet_state.treasury_bump
-    )]
-    pub wallet_treasury: Account<'info, VaultTreasury>,
-    /// CHECK: destination validated on transfer
-    #[account(mut)]
-    pub destination: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
+fn consume_session_call(
+    session: &mut SessionKeyAccount,
+    wallet: &impl WalletAccount,
+    wallet_key: &Pubkey,
+    authority: &Pubkey,
+) -> Result<()> {
+    require!(
+        session.wallet == *wallet_key,
+        AlphaError::SessionKeyWalletMismatch
+    );
+    require!(
+        session.epoch == wallet.session_nonce,
+        AlphaError::SessionEpochRevoked
+    );
+    require!(wallet.delegates_frozen == 0, AlphaError::DelegatesFrozen);
+    require!(
+        session.authority == *authority,
+        AlphaError::WithdrawAuthorisationFailed
+    );
+
+    if let Some(limit) = session.remaining_calls {
+        require!(limit > 0, AlphaError::SessionKeyExhausted);
+        session.remaining_calls = Some(limit - 1);
+    }
+
+    let clock = Clock::get()?;
+    if let Some(expiry) = session.expires_at {
+        require!(!expiry.has_passed(&clock), AlphaError::SessionKeyExpired);
+    }
+
+    if let Some(rate_limit) = session.rate_limit.as_mut() {
+        rate_limit.roll_period(&clock);
+        let calls = rate_limit.calls_in_period.saturating_add(1);
+        if let Some(calls_per_period) = rate_limit.calls_per_period {
+            require!(calls <= calls_per_period, AlphaError::SessionRateLimited);
+        }
+        rate_limit.calls_in_period = calls;
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct GuardianAction<'info> {
-    pub guardian: Signer<'info>,
-    #[account(mut)]
-    pub wallet_state: Account<'info, WalletState>,
+// Called after `consume_session_call`, so the rate limit period has already been rolled.
+fn charge_session_value(session: &mut SessionKeyAccount, amount: u64) -> Result<()> {
+    if let Some(value_limit) = session.remaining_value {
+        require!(value_limit >= amount, AlphaError::InsufficientVaultBalance);
+        session.remaining_value = Some(value_limit - amount);
+    }
+
+    if let Some(rate_limit) = session.rate_limit.as_mut() {
+        let spent = rate_limit.spent_in_period.saturating_add(amount);
+        if let Some(value_per_period) = rate_limit.value_per_period {
+            require!(spent <= value_per_period, AlphaError::SessionRateLimited);
+        }
+        rate_limit.spent_in_period = spent;
+    }
+    Ok(())
 }
 
-#[event]
-pub struct VaultDepositEvent {
-    pub vault: Pubkey,
-    pub owner: Pubkey,
-    pub amount: u64,
-    pub balance_after: u64,
+fn charge_session_tokens(
+    session: &mut SessionKeyAccount,
+    mint: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let allowance = session
+        .token_allowance_mut(mint)
+        .ok_or(AlphaError::SessionMintNotAuthorised)?;
+    require!(
+        allowance.amount >= amount,
+        AlphaError::InsufficientVaultBalance
+    );
+    allowance.amount -= amount;
+    Ok(())
 }
 
-#[event]
-pub struct VaultWithdrawalEvent {
-    pub vault: Pubkey,
-    pub owner: Pubkey,
-    pub recipient: Pubkey,
-    pub amount: u64,
-    pub balance_after: u64,
+// Token-2022 accounts are metered like SPL token accounts, otherwise a session allowed to call it
+// could move those balances without touching its allowances.
+fn is_token_program(program: &Pubkey) -> bool {
+    *program == token::ID || *program == token_2022::ID
 }
 
-#[event]
-pub struct WalletTransferEvent {
-    pub wallet: Pubkey,
-    pub actor: Pubkey,
-    pub destination: Pubkey,
-    pub amount: u64,
-    pub memo: Option<Vec<u8>>,
-    pub via_session: bool,
+fn treasury_token_accounts(
+    treasury_key: &Pubkey,
+    remaining: &[AccountInfo<'_>],
+) -> Vec<(usize, token_interface::TokenAccount)> {
+    let mut accounts: Vec<(usize, token_interface::TokenAccount)> = Vec::new();
+    for (index, info) in remaining.iter().enumerate() {
+        if !info.is_writable
+            || !is_token_program(info.owner)
+            || remaining[..index].iter().any(|other| other.key == info.key)
+        {
+            continue;
+        }
+        let Ok(data) = info.try_borrow_data() else {
+            continue;
+        };
+        if let Ok(account) = token_interface::TokenAccount::try_deserialize(&mut &data[..]) {
+            if account.owner == *treasury_key {
+                accounts.push((index, account));
+            }
+        }
+    }
+    accounts
 }
 
-#[event]
-pub struct SessionKeyRegistered {
-    pub wallet: Pubkey,
-    pub authority: Pubkey,
-    pub expires_at_slot: Option<u64>,
-    pub usage_limit: Option<u64>,
+// Anything a CPI can pay into is writable, so with an allowlist every writable account other than
+// the treasury and its own token accounts must be listed, either directly or as a token account's
+// owner.
+fn require_session_destinations(
+    session: &SessionKeyAccount,
+    treasury_key: &Pubkey,
+    account_indexes: &[u8],
+    remaining: &[AccountInfo<'_>],
+    treasury_accounts: &[(usize, token_interface::TokenAccount)],
+) -> Result<()> {
+    if session.allowed_destinations.is_empty() {
+        return Ok(());
+    }
+    for index in account_indexes {
+        let info = remaining
+            .get(*index as usize)
+            .ok_or(AlphaError::WalletInstructionAccountMissing)?;
+        if !info.is_writable
+            || info.key == treasury_key
+            || treasury_accounts
+                .iter()
+                .any(|(treasury_index, _)| remaining[*treasury_index].key == info.key)
+        {
+            continue;
+        }
+        let mut candidates = vec![*info.key];
+        if is_token_program(info.owner) {
+            let data = info.try_borrow_data()?;
+            if let Ok(account) = token_interface::TokenAccount::try_deserialize(&mut &data[..]) {
+                candidates.push(account.owner);
+            }
+        }
+        require!(
+            session.allows_destination(&candidates),
+            AlphaError::SessionDestinationNotAllowed
+        );
+    }
+    Ok(())
 }
 
-#[event]
-pub struct SessionKeyRevoked {
-    pub wallet: Pubkey,
-    pub authority: Pubkey,
+// Balance decreases of the snapshotted treasury token accounts, by mint. A closed account, or a
+// changed owner, delegate or close authority, would let the balance leave later without being
+// metered, so it fails with `modified`.
+fn treasury_token_outflows(
+    remaining: &[AccountInfo<'_>],
+    before: &[(usize, token_interface::TokenAccount)],
+    modified: AlphaError,
+) -> Result<Vec<(Pubkey, u64)>> {
+    let mut outflows = Vec::new();
+    for (index, previous) in before {
+        let info = &remaining[*index];
+        if !is_token_program(info.owner) {
+            return Err(error!(modified));
+        }
+        let current = {
+            let data = info.try_borrow_data()?;
+            token_interface::TokenAccount::try_deserialize(&mut &data[..])
+                .map_err(|_| error!(modified))?
+        };
+        if current.owner != previous.owner
+            || current.delegate != previous.delegate
+            || current.close_authority != previous.close_authority
+        {
+            return Err(error!(modified));
+        }
+        let spent = previous.amount.saturating_sub(current.amount);
+        if spent > 0 {
+            outflows.push((previous.mint, spent));
+        }
+    }
+    Ok(outflows)
 }
 
-#[event]
-pub struct RecoveryProposed {
-    pub wallet: Pubkey,
-    pub guardian: Pubkey,
-    pub execute_after_slot: u64,
-}
+fn charge_owner_quota(
+    wallet: &mut impl WalletAccountMut,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let quota = wallet
+        .owner_quota_mut(owner)
+        .ok_or(AlphaError::OwnerThresholdNotMet)?;
 
-#[event]
-pub struct RecoveryVote {
-    pub wallet: Pubkey,
-    pub guardian: Pubkey,
-    pub approvals: u8,
+    let clock = Clock::get()?;
+    quota.roll_period(&clock);
+    let spent = quota
+        .spent_in_period
+        .checked_add(amount)
+        .ok_or(AlphaError::OwnerThresholdNotMet)?;
+    require!(spent <= quota.allowance, AlphaError::OwnerThresholdNotMet);
+    quota.spent_in_period = spent;
+    Ok(())
 }
 
-#[event]
-pub struct RecoveryCompleted {
-    pub wallet: Pubkey,
-    pub executed_slot: u64,
+fn resolve_operator<'a>(
+    wallet: &'a mut impl WalletAccountMut,
+    signer: &Pubkey,
+    scope: u8,
+) -> Result<&'a mut OperatorEntry> {
+    require!(wallet.delegates_frozen == 0, AlphaError::DelegatesFrozen);
+    let clock = Clock::get()?;
+    let entry = wallet
+        .operator_mut(signer)
+        .ok_or(AlphaError::OperatorNotConfigured)?;
+    require!(
+        entry.scopes & scope == scope,
+        AlphaError::OperatorScopeNotAllowed
+    );
+    if let Some(expires_at) = entry.expires_at() {
+        require!(!expires_at.has_passed(&clock), AlphaError::OperatorExpired);
+    }
+    Ok(entry)
 }
 
-fn validate_owner_set(owners: &[OwnerShare]) -> Result<()> {
-    require!(!owners.is_empty(), AlphaError::OwnerThresholdNotMet);
-    require!(owners.len() <= MAX_OWNERS, AlphaError::TooManyOwners);
+fn enforce_operator_policy(
+    policy: &mut OperatorPolicy,
+    destination: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        policy.allows_destination(&[*destination]),
+        AlphaError::OperatorDestinationNotAllowed
+    );
+    require!(
+        amount <= policy.max_single_transfer,
+        AlphaError::OperatorTransferTooLarge
+    );
 
-    let mut seen: Vec<Pubkey> = Vec::with_capacity(owners.len());
-    for owner in owners {
-        require!(owner.weight > 0, AlphaError::OwnerThresholdNotMet);
-        if seen.contains(&owner.owner) {
-            return err!(AlphaError::DuplicateOwner);
-        }
-        seen.push(owner.owner);
-    }
+    let clock = Clock::get()?;
+    policy.roll_period(&clock);
+    let spent = policy
+        .spent_in_period
+        .checked_add(amount)
+        .ok_or(AlphaError::OperatorAllowanceExceeded)?;
+    require!(
+        spent <= policy.period_allowance,
+        AlphaError::OperatorAllowanceExceeded
+    );
+    policy.spent_in_period = spent;
     Ok(())
 }
 
-fn validate_guardians(guardians: &[Pubkey], quorum: u8) -> Result<()> {
+fn ensure_proposal_open(proposal: &Proposal, clock: &Clock) -> Result<()> {
     require!(
-        guardians.len() <= MAX_GUARDIANS,
-        AlphaError::TooManyGuardians
+        proposal.status == ProposalStatus::Active,
+        AlphaError::ProposalNotActive
     );
-    if guardians.is_empty() {
-        require!(quorum == 0, AlphaError::GuardianQuorumNotMet);
-        return Ok(());
-    }
     require!(
-        quorum > 0 && quorum as usize <= guardians.len(),
-        AlphaError::GuardianQuorumNotMet
+        clock.slot <= proposal.expires_at_slot,
+        AlphaError::ProposalExpired
     );
+    Ok(())
+}
 
-    let mut seen: Vec<Pubkey> = Vec::with_capacity(guardians.len());
-    for guardian in guardians {
-        if seen.contains(guardian) {
-            return err!(AlphaError::DuplicateGuardian);
-        }
-        seen.push(*guardian);
+fn record_proposal_vote(
+    wallet: &impl WalletAccount,
+    proposal: &mut Proposal,
+    owner: Pubkey,
+    approve: bool,
+) -> Result<()> {
+    require!(
+        wallet.owner_weight(&owner).is_some(),
+        AlphaError::NotWalletOwner
+    );
+    let clock = Clock::get()?;
+    ensure_proposal_open(proposal, &clock)?;
+
+    // Votes from owners rotated out since the proposal was created no longer count.
+    proposal
+        .approvals
+        .retain(|voter| wallet.owner_weight(voter).is_some());
+    proposal
+        .rejections
+        .retain(|voter| wallet.owner_weight(voter).is_some());
+
+    require!(
+        !proposal.approvals.contains(&owner) && !proposal.rejections.contains(&owner),
+        AlphaError::ProposalAlreadyVoted
+    );
+
+    if approve {
+        proposal.approvals.push(owner);
+    } else {
+        proposal.rejections.push(owner);
     }
     Ok(())
 }
 
-fn verify_owner_threshold<'info>(
-    wallet: &WalletState,
-    primary: &Signer<'info>,
-    remaining: &[AccountInfo<'info>],
+fn new_session_state(
+    wallet: &mut impl WalletAccountMut,
+    wallet_key: Pubkey,
+    authority: Pubkey,
+    config: SessionConfig,
+    bump: u8,
+    clock: &Clock,
+) -> SessionKeyAccount {
+    wallet.active_sessions = wallet
+        .active_sessions
+        .checked_add(1)
+        .expect("active session overflow");
+
+    SessionKeyAccount {
+        version: SessionKeyAccount::VERSION,
+        wallet: wallet_key,
+        authority,
+        epoch: wallet.session_nonce,
+        expires_at: config.expires_at,
+        remaining_calls: config.usage_limit,
+        remaining_value: config.value_limit,
+        allowed_programs: config.allowed_programs,
+        bump,
+        token_allowances: config.token_allowances,
+        rate_limit: config
+            .rate_limit
+            .map(|rate_limit| SessionRateLimit::from_config(rate_limit, clock)),
+        allowed_destinations: config.allowed_destinations,
+        instruction_templates: config.instruction_templates,
+    }
+}
+
+// The treasury holds data, so it cannot fund `create_account`; its lamports are moved directly.
+fn create_session_account<'info>(
+    treasury: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    session: &SessionKeyAccount,
 ) -> Result<()> {
-    let mut total_weight: u64 = 0;
-    let mut seen: Vec<Pubkey> = Vec::new();
+    let space = SessionKeyAccount::space(MAX_SESSION_PROGRAMS, MAX_SESSION_TOKEN_ALLOWANCES);
+    let rent = Rent::get()?;
+    let shortfall = rent
+        .minimum_balance(space)
+        .saturating_sub(target.lamports());
+    let spare = treasury
+        .lamports()
+        .saturating_sub(rent.minimum_balance(treasury.data_len()));
+    require!(spare >= shortfall, AlphaError::TreasuryRentShortfall);
 
-    accumulate_owner_weight(
-        wallet,
-        &mut total_weight,
-        &mut seen,
-        &primary.to_account_info(),
+    let seeds: &[&[u8]] = &[
+        b"session-key",
+        session.wallet.as_ref(),
+        session.authority.as_ref(),
+        &[session.bump],
+    ];
+    let allocate_accounts = system_program::Allocate {
+        account_to_allocate: target.clone(),
+    };
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            allocate_accounts,
+            &[seeds],
+        ),
+        space as u64,
     )?;
-    for account in remaining {
-        accumulate_owner_weight(wallet, &mut total_weight, &mut seen, account)?;
+    let assign_accounts = system_program::Assign {
+        account_to_assign: target.clone(),
+    };
+    system_program::assign(
+        CpiContext::new_with_signer(system_program.to_account_info(), assign_accounts, &[seeds]),
+        &crate::ID,
+    )?;
+    // Moved only after the CPIs, which would otherwise see unbalanced lamports on the treasury.
+    **treasury.try_borrow_mut_lamports()? -= shortfall;
+    **target.try_borrow_mut_lamports()? += shortfall;
+
+    let mut data = target.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    session.try_serialize(&mut writer)
+}
+
+// `create_account` fails on a prefunded address, so only the rent shortfall is transferred before
+// allocating and assigning.
+fn create_program_account<'info, T: AccountSerialize>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    signer_seeds: &[&[u8]],
+    space: usize,
+    state: &T,
+) -> Result<()> {
+    let shortfall = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(target.lamports());
+    if shortfall > 0 {
+        let transfer_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: target.clone(),
+        };
+        system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), transfer_accounts),
+            shortfall,
+        )?;
     }
+    let allocate_accounts = system_program::Allocate {
+        account_to_allocate: target.clone(),
+    };
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            allocate_accounts,
+            &[signer_seeds],
+        ),
+        space as u64,
+    )?;
+    let assign_accounts = system_program::Assign {
+        account_to_assign: target.clone(),
+    };
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            assign_accounts,
+            &[signer_seeds],
+        ),
+        &crate::ID,
+    )?;
+
+    let mut data = target.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    state.try_serialize(&mut writer)
+}
 
+fn ensure_guardian_member(wallet: &impl WalletAccount, guardian: &Pubkey) -> Result<()> {
     require!(
-        total_weight >= wallet.threshold as u64,
-        AlphaError::OwnerThresholdNotMet
+        wallet.guardians().contains(guardian),
+        AlphaError::GuardianNotFound
     );
     Ok(())
 }
 
-fn accumulate_owner_weight<'info>(
-    wallet: &WalletState,
-    total_weight: &mut u64,
-    seen: &mut Vec<Pubkey>,
-    account: &AccountInfo<'info>,
+// Without a timelock nothing is snapshotted and the instructions run as they are.
+fn invoke_timelocked_instructions(
+    wallet_key: Pubkey,
+    treasury_bump: u8,
+    treasury_key: Pubkey,
+    timelock: Option<&TransferTimelock>,
+    instructions: &[WalletInstruction],
+    remaining: &[AccountInfo<'_>],
 ) -> Result<()> {
-    if !account.is_signer {
-        return Ok(());
-    }
+    let token_accounts = match timelock {
+        Some(_) => treasury_token_accounts(&treasury_key, remaining),
+        None => Vec::new(),
+    };
+    invoke_wallet_instructions(
+        wallet_key,
+        treasury_bump,
+        treasury_key,
+        instructions,
+        remaining,
+    )?;
+    let outflows = treasury_token_outflows(
+        remaining,
+        &token_accounts,
+        AlphaError::TransferTimelockActive,
+    )?;
+    require_token_outflows_below_timelock(timelock, &outflows)
+}
 
-    if seen.contains(account.key) {
-        return Ok(());
-    }
+fn invoke_wallet_instructions(
+    wallet_key: Pubkey,
+    treasury_bump: u8,
+    treasury_key: Pubkey,
+    instructions: &[WalletInstruction],
+    remaining: &[AccountInfo<'_>],
+) -> Result<()> {
+    let seeds: &[&[u8]] = &[b"wallet-treasury", wallet_key.as_ref(), &[treasury_bump]];
 
-    if let Some(weight) = wallet.owner_weight(account.key) {
-        *total_weight = total_weight
-            .checked_add(weight as u64)
-            .expect("weight overflow");
-        seen.push(*account.key);
+    for instruction in instructions {
+        let mut metas = Vec::with_capacity(instruction.account_indexes.len());
+        let mut infos = Vec::with_capacity(instruction.account_indexes.len() + 1);
+        for index in &instruction.account_indexes {
+            let info = remaining
+                .get(*index as usize)
+                .ok_or(AlphaError::WalletInstructionAccountMissing)?;
+            let is_signer = info.is_signer || *info.key == treasury_key;
+            metas.push(if info.is_writable {
+                AccountMeta::new(*info.key, is_signer)
+            } else {
+                AccountMeta::new_readonly(*info.key, is_signer)
+            });
+            infos.push(info.clone());
+        }
+
+        let program_info = remaining
+            .iter()
+            .find(|info| *info.key == instruction.program_id)
+            .ok_or(AlphaError::WalletInstructionAccountMissing)?;
+        infos.push(program_info.clone());
+
+        invoke_signed(
+            &Instruction {
+                program_id: instruction.program_id,
+                accounts: metas,
+                data: instruction.data.clone(),
+            },
+            &infos,
+            &[seeds],
+        )?;
     }
 
     Ok(())
 }
 
-fn ensure_guardian_member(wallet: &WalletState, guardian: &Pubkey) -> Result<()> {
+fn sweep_token_account<'info>(
+    wallet_key: Pubkey,
+    treasury_bump: u8,
+    treasury: &Account<'info, VaultTreasury>,
+    source: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    rent_destination: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<(Pubkey, u64)> {
+    require_keys_eq!(*source.owner, token::ID, AlphaError::InvalidSweepAccount);
+    let source_account = {
+        let data = source.try_borrow_data()?;
+        let mut slice: &[u8] = &data;
+        TokenAccount::try_deserialize(&mut slice)?
+    };
+    require_keys_eq!(
+        source_account.owner,
+        treasury.key(),
+        AlphaError::InvalidSweepAccount
+    );
+
+    let seeds = &[b"wallet-treasury", wallet_key.as_ref(), &[treasury_bump]];
+
+    if source_account.amount > 0 {
+        let transfer_accounts = token::Transfer {
+            from: source.clone(),
+            to: destination.clone(),
+            authority: treasury.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                transfer_accounts,
+                &[seeds],
+            ),
+            source_account.amount,
+        )?;
+    }
+
+    let close_accounts = token::CloseAccount {
+        account: source.clone(),
+        destination: rent_destination.clone(),
+        authority: treasury.to_account_info(),
+    };
+    token::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        close_accounts,
+        &[seeds],
+    ))?;
+    Ok((source_account.mint, source_account.amount))
+}
+
+fn force_close_sessions<'info>(
+    wallet: &mut impl WalletAccountMut,
+    wallet_key: Pubkey,
+    accounts: &[AccountInfo<'info>],
+    rent_destination: &AccountInfo<'info>,
+) -> Result<u32> {
+    let mut closed = 0;
+    for info in accounts {
+        if *info.owner != crate::ID || !info.is_writable {
+            continue;
+        }
+        let session = {
+            let data = info.try_borrow_data()?;
+            let mut slice: &[u8] = &data;
+            match SessionKeyAccount::try_deserialize(&mut slice) {
+                Ok(session) => session,
+                Err(_) => continue,
+            }
+        };
+        require_keys_eq!(
+            session.wallet,
+            wallet_key,
+            AlphaError::SessionKeyWalletMismatch
+        );
+
+        let lamports = info.lamports();
+        **rent_destination.try_borrow_mut_lamports()? = rent_destination
+            .lamports()
+            .checked_add(lamports)
+            .expect("lamport overflow");
+        **info.try_borrow_mut_lamports()? = 0;
+        info.assign(&system_program::ID);
+        info.realloc(0, false)?;
+
+        wallet.active_sessions = wallet.active_sessions.saturating_sub(1);
+        closed += 1;
+
+        emit!(SessionKeyRevoked {
+            wallet: wallet_key,
+            authority: session.authority,
+        });
+    }
+    Ok(closed)
+}
+
+fn migrate_versioned<'info, T: VersionedAccount + AccountSerialize>(
+    target: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    surplus: Option<&AccountInfo<'info>>,
+    system_program: &Program<'info, System>,
+    space: impl FnOnce(&mut T) -> usize,
+) -> Result<T> {
+    let (from_version, mut account) = {
+        let data = target.try_borrow_data()?;
+        T::from_versioned(&data)?
+    };
     require!(
-        wallet.guardians.guardians.contains(guardian),
-        AlphaError::GuardianNotFound
+        from_version < T::VERSION,
+        AlphaError::AccountAlreadyMigrated
     );
+    let space = space(&mut account);
+    fund_migration(target, payer, surplus, system_program, space)?;
+    target.realloc(space, true)?;
+
+    {
+        let mut data = target.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        account.try_serialize(&mut writer)?;
+    }
+
+    emit!(AccountMigrated {
+        account: target.key(),
+        from_version,
+        to_version: T::VERSION,
+    });
+
+    Ok(account)
+}
+
+// Brings a migrating account to rent exemption at its new size. The payer covers a shortfall; a
+// surplus goes to `surplus` (the wallet treasury) or stays on the account, never to the payer.
+fn fund_migration<'info>(
+    target: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    surplus: Option<&AccountInfo<'info>>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(space);
+    let current = target.lamports();
+    if let Some(surplus) = surplus.filter(|_| current > required) {
+        // Right-sized layouts can be smaller than the legacy allocation.
+        **target.try_borrow_mut_lamports()? -= current - required;
+        **surplus.try_borrow_mut_lamports()? += current - required;
+    }
+    let shortfall = required.saturating_sub(current);
+    if shortfall > 0 {
+        let transfer_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: target.clone(),
+        };
+        system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), transfer_accounts),
+            shortfall,
+        )?;
+    }
+    Ok(())
+}
+
+// Grows a wallet to the largest layout so any section can be extended in place; handlers call
+// `resize_wallet_state` once they are done.
+fn reserve_wallet_state(wallet: &AccountLoader<'_, WalletState>) -> Result<()> {
+    wallet.as_ref().realloc(WalletState::MAX_LEN, false)?;
+    Ok(())
+}
+
+// Trims the wallet account to its exact size; the treasury funds growth and takes back refunds.
+fn resize_wallet_state<'info>(
+    wallet: &AccountLoader<'info, WalletState>,
+    treasury: &Account<'info, VaultTreasury>,
+) -> Result<()> {
+    let wallet_info = wallet.to_account_info();
+    let space = wallet.load()?.current_space();
+    wallet_info.realloc(space, false)?;
+
+    let treasury_info = treasury.to_account_info();
+    let rent = Rent::get()?;
+    let required = rent.minimum_balance(space);
+    let current = wallet_info.lamports();
+    if required > current {
+        let shortfall = required - current;
+        let spare = treasury_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(treasury_info.data_len()));
+        require!(spare >= shortfall, AlphaError::TreasuryRentShortfall);
+        **treasury_info.try_borrow_mut_lamports()? -= shortfall;
+        **wallet_info.try_borrow_mut_lamports()? += shortfall;
+    } else {
+        let excess = current - required;
+        **wallet_info.try_borrow_mut_lamports()? -= excess;
+        **treasury_info.try_borrow_mut_lamports()? += excess;
+    }
     Ok(())
 }
 
 fn transfer_from_wallet<'info>(
-    wallet: &Account<'info, WalletState>,
+    wallet_key: Pubkey,
     treasury: &Account<'info, VaultTreasury>,
     destination: &AccountInfo<'info>,
     amount: u64,
-    system_program: &Program<'info, System>,
 ) -> Result<()> {
     require!(
-        treasury.vault == wallet.key(),
+        treasury.vault == wallet_key,
         AlphaError::VaultBalanceMissing
     );
+    debit_treasury(&treasury.to_account_info(), destination, amount)
+}
 
-    let seeds = &[
-        b"wallet-treasury",
-        wallet.key().as_ref(),
-        &[wallet.treasury_bump],
-    ];
+// Treasuries are program-owned and hold data, so the system program cannot debit them; lamports are
+// moved directly and the treasury has to stay rent exempt.
+fn debit_treasury<'info>(
+    treasury: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let spare = treasury
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(treasury.data_len()));
+    require!(spare >= amount, AlphaError::InsufficientVaultBalance);
+
+    let debited = treasury
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(AlphaError::InsufficientVaultBalance)?;
+    **treasury.try_borrow_mut_lamports()? = debited;
+    let credited = destination
+        .lamports()
+        .checked_add(amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **destination.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transfer_tokens_from_wallet<'info>(
+    wallet_key: Pubkey,
+    treasury_bump: u8,
+    treasury: &Account<'info, VaultTreasury>,
+    source: &Account<'info, TokenAccount>,
+    mint: &Account<'info, Mint>,
+    destination: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        treasury.vault == wallet_key,
+        AlphaError::VaultBalanceMissing
+    );
+
+    let seeds = &[b"wallet-treasury", wallet_key.as_ref(), &[treasury_bump]];
 
-    let transfer_accounts = system_program::Transfer {
-        from: treasury.to_account_info(),
-        to: destination.clone(),
+    let transfer_accounts = token::TransferChecked {
+        from: source.to_account_info(),
+        mint: mint.to_account_info(),
+        to: destination.to_account_info(),
+        authority: treasury.to_account_info(),
     };
 
-    system_program::transfer(
-        CpiContext::new_with_signer(
-            system_program.to_account_info(),
-            transfer_accounts,
-            &[seeds],
-        ),
+    token::transfer_checked(
+        CpiContext::new_with_signer(token_program.to_account_info(), transfer_accounts, &[seeds]),
         amount,
+        mint.decimals,
     )
 }
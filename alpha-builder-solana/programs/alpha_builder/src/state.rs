@@ -1,15 +1,50 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use bytemuck::{Pod, Zeroable};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::cell::{Ref, RefMut};
+use std::ops::{Deref, DerefMut, Range};
+
+use self::constants::{
+    ACTION_CLASS_COUNT, MAX_ADDRESS_BOOK_ENTRIES, MAX_GUARDIANS, MAX_GUARDIAN_VOTES,
+    MAX_INSTRUCTION_ACCOUNTS, MAX_INSTRUCTION_DATA_LENGTH, MAX_MEMO_LENGTH, MAX_OPERATORS,
+    MAX_OPERATOR_DESTINATIONS, MAX_OWNERS, MAX_REGISTERED_SESSIONS, MAX_SESSION_DESTINATIONS,
+    MAX_SESSION_PROGRAMS, MAX_SESSION_TEMPLATES, MAX_SESSION_TOKEN_ALLOWANCES,
+    MAX_TEMPLATE_DISCRIMINATOR_LENGTH, MAX_TEMPLATE_FIXED_ACCOUNTS, MAX_WALLET_INSTRUCTIONS,
+    OPERATOR_SCOPE_ALL, OWNER_ROLE_ALL, OWNER_ROLE_CANCELLER,
+};
+use crate::errors::AlphaError;
 
 pub mod constants {
     pub const MAX_OWNERS: usize = 10;
     pub const MAX_GUARDIANS: usize = 10;
     pub const MAX_GUARDIAN_VOTES: usize = 10;
     pub const MAX_SESSION_PROGRAMS: usize = 8;
+    pub const MAX_SESSION_TOKEN_ALLOWANCES: usize = 4;
+    pub const MAX_SESSION_DESTINATIONS: usize = 8;
+    pub const MAX_REGISTERED_SESSIONS: usize = 16;
+    pub const MAX_SESSION_TEMPLATES: usize = 4;
+    pub const MAX_TEMPLATE_DISCRIMINATOR_LENGTH: usize = 8;
+    pub const MAX_TEMPLATE_FIXED_ACCOUNTS: usize = 4;
+    pub const MAX_OPERATORS: usize = 4;
+    pub const MAX_OPERATOR_DESTINATIONS: usize = 4;
+    pub const OPERATOR_SCOPE_TRANSFER: u8 = 1 << 0;
+    pub const OPERATOR_SCOPE_TOKEN_TRANSFER: u8 = 1 << 1;
+    pub const OPERATOR_SCOPE_ALL: u8 = OPERATOR_SCOPE_TRANSFER | OPERATOR_SCOPE_TOKEN_TRANSFER;
     pub const MAX_MEMO_LENGTH: usize = 128;
+    pub const MAX_WALLET_INSTRUCTIONS: usize = 4;
+    pub const MAX_INSTRUCTION_ACCOUNTS: usize = 16;
+    pub const MAX_INSTRUCTION_DATA_LENGTH: usize = 256;
+    pub const MAX_ADDRESS_BOOK_ENTRIES: usize = 32;
+    pub const ACTION_CLASS_COUNT: usize = 7;
+    pub const OWNER_ROLE_PROPOSER: u8 = 1 << 0;
+    pub const OWNER_ROLE_EXECUTOR: u8 = 1 << 1;
+    pub const OWNER_ROLE_CANCELLER: u8 = 1 << 2;
+    pub const OWNER_ROLE_ALL: u8 = OWNER_ROLE_PROPOSER | OWNER_ROLE_EXECUTOR | OWNER_ROLE_CANCELLER;
 }
 
+#[repr(C)]
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
@@ -18,6 +53,8 @@ pub mod constants {
     Debug,
     PartialEq,
     Eq,
+    Pod,
+    Zeroable,
     Serialize,
     Deserialize,
     JsonSchema,
@@ -33,64 +70,221 @@ impl OwnerShare {
     }
 }
 
-#[derive(
-    AnchorSerialize,
-    AnchorDeserialize,
-    Clone,
-    Debug,
-    PartialEq,
-    Eq,
-    Serialize,
-    Deserialize,
-    JsonSchema,
-)]
-pub struct GuardianSet {
-    pub guardians: Vec<Pubkey>,
-    pub quorum: u8,
-    pub cooldown_slots: u64,
-    pub active_recovery: Option<RecoveryProposal>,
+#[zero_copy]
+pub struct RecoveryProposal {
+    pub proposed_slot: u64,
+    // Measured in the wallet's `guardian_cooldown_unit`.
+    pub execute_after: u64,
+    pub new_owners: [OwnerShare; MAX_OWNERS],
+    pub approvals: [Pubkey; MAX_GUARDIAN_VOTES],
+    pub new_threshold: u16,
+    pub new_owner_count: u8,
+    pub approval_count: u8,
 }
 
-impl GuardianSet {
-    pub fn base_space(max_guardians: usize) -> usize {
-        4 + max_guardians * 32 + 1 + 8 + 1
+impl RecoveryProposal {
+    pub fn new(
+        proposed_slot: u64,
+        execute_after: u64,
+        new_threshold: u16,
+        new_owners: &[OwnerShare],
+        approvals: &[Pubkey],
+    ) -> Self {
+        let mut recovery = Self::zeroed();
+        recovery.proposed_slot = proposed_slot;
+        recovery.execute_after = execute_after;
+        recovery.new_threshold = new_threshold;
+        recovery.new_owners[..new_owners.len()].copy_from_slice(new_owners);
+        recovery.new_owner_count = new_owners.len() as u8;
+        recovery.approvals[..approvals.len()].copy_from_slice(approvals);
+        recovery.approval_count = approvals.len() as u8;
+        recovery
+    }
+
+    pub fn new_owners(&self) -> &[OwnerShare] {
+        &self.new_owners[..self.new_owner_count as usize]
+    }
+
+    pub fn approvals(&self) -> &[Pubkey] {
+        &self.approvals[..self.approval_count as usize]
+    }
+
+    pub fn add_approval(&mut self, guardian: Pubkey) {
+        self.approvals[self.approval_count as usize] = guardian;
+        self.approval_count += 1;
     }
 }
 
+#[repr(C)]
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
     Clone,
+    Copy,
     Debug,
     PartialEq,
     Eq,
+    Pod,
+    Zeroable,
     Serialize,
     Deserialize,
     JsonSchema,
 )]
-pub struct RecoveryProposal {
-    pub proposed_slot: u64,
-    pub execute_after_slot: u64,
-    pub new_threshold: u16,
-    pub new_owners: Vec<OwnerShare>,
-    pub approvals: Vec<Pubkey>,
+pub struct WalletCapacity {
+    pub max_owners: u8,
+    pub max_guardians: u8,
 }
 
-impl RecoveryProposal {
-    pub fn space(max_owners: usize, max_guardians: usize) -> usize {
-        8 + 8 + 2 + (4 + max_owners * OwnerShare::space()) + (4 + max_guardians * 32)
+impl Default for WalletCapacity {
+    fn default() -> Self {
+        Self {
+            max_owners: MAX_OWNERS as u8,
+            max_guardians: MAX_GUARDIANS as u8,
+        }
+    }
+}
+
+impl WalletCapacity {
+    pub fn space() -> usize {
+        1 + 1
+    }
+}
+
+pub trait VersionedAccount: AccountDeserialize + Discriminator + Sized {
+    const VERSION: u8;
+    type V0: AnchorDeserialize;
+
+    fn version(&self) -> u8;
+    fn v0_len() -> usize;
+    fn upgrade_v0(legacy: Self::V0) -> Self;
+
+    // Returns the stored version with the account upgraded to the current layout.
+    fn from_versioned(data: &[u8]) -> Result<(u8, Self)> {
+        require!(
+            data.len() >= 8 && data[..8] == Self::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        // v0 layouts predate the version byte; their fixed allocation identifies them.
+        if data.len() == Self::v0_len() {
+            let mut slice: &[u8] = &data[8..];
+            let legacy = Self::V0::deserialize(&mut slice)
+                .map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))?;
+            return Ok((0, Self::upgrade_v0(legacy)));
+        }
+
+        let mut slice: &[u8] = data;
+        let account = Self::try_deserialize(&mut slice)?;
+        require!(
+            account.version() == Self::VERSION,
+            AlphaError::UnsupportedAccountVersion
+        );
+        Ok((account.version(), account))
+    }
+}
+
+// Borsh layouts of the deployed program, before accounts carried a version byte.
+pub mod v0 {
+    use super::OwnerShare;
+    use anchor_lang::prelude::*;
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct GuardianSet {
+        pub guardians: Vec<Pubkey>,
+        pub quorum: u8,
+        pub cooldown_slots: u64,
+        pub active_recovery: Option<RecoveryProposal>,
+    }
+
+    impl GuardianSet {
+        pub fn base_space(max_guardians: usize) -> usize {
+            4 + max_guardians * 32 + 1 + 8 + 1
+        }
+    }
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct RecoveryProposal {
+        pub proposed_slot: u64,
+        pub execute_after_slot: u64,
+        pub new_threshold: u16,
+        pub new_owners: Vec<OwnerShare>,
+        pub approvals: Vec<Pubkey>,
+    }
+
+    impl RecoveryProposal {
+        pub fn space(max_owners: usize, max_guardians: usize) -> usize {
+            8 + 8 + 2 + (4 + max_owners * OwnerShare::space()) + (4 + max_guardians * 32)
+        }
+    }
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct VaultConfig {
+        pub admin: Pubkey,
+        pub operator: Option<Pubkey>,
+        pub bump: u8,
+    }
+
+    impl VaultConfig {
+        pub const LEN: usize = 8 + 32 + 1 + 32 + 1;
+    }
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct VaultBalance {
+        pub owner: Pubkey,
+        pub amount: u64,
+        pub bump: u8,
+    }
+
+    impl VaultBalance {
+        pub const LEN: usize = 8 + 32 + 8 + 1;
+    }
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct WalletState {
+        pub owners: Vec<OwnerShare>,
+        pub threshold: u16,
+        pub guardians: GuardianSet,
+        pub session_nonce: u64,
+        pub treasury_bump: u8,
+        pub operator_delegate: Option<Pubkey>,
+    }
+
+    impl WalletState {
+        pub fn space(max_owners: usize, max_guardians: usize) -> usize {
+            let owners_space = 4 + max_owners * OwnerShare::space();
+            let guardian_space = GuardianSet::base_space(max_guardians)
+                + RecoveryProposal::space(max_owners, max_guardians);
+            8 + owners_space + 2 + guardian_space + 8 + 1 + 1 + 32
+        }
+    }
+
+    #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+    pub struct SessionKeyAccount {
+        pub wallet: Pubkey,
+        pub authority: Pubkey,
+        pub expires_at_slot: Option<u64>,
+        pub remaining_calls: Option<u64>,
+        pub remaining_value: Option<u64>,
+        pub allowed_programs: Vec<Pubkey>,
+        pub bump: u8,
+    }
+
+    impl SessionKeyAccount {
+        pub fn space(max_programs: usize) -> usize {
+            8 + 32 + 32 + 1 + 8 + 1 + 8 + 1 + 8 + (4 + max_programs * 32) + 1
+        }
     }
 }
 
 #[account]
 pub struct VaultConfig {
+    pub version: u8,
     pub admin: Pubkey,
     pub operator: Option<Pubkey>,
     pub bump: u8,
 }
 
 impl VaultConfig {
-    pub const LEN: usize = 8 + 32 + 1 + 32 + 1;
+    pub const LEN: usize = 8 + 1 + 32 + 1 + 32 + 1;
 }
 
 #[account]
@@ -105,39 +299,612 @@ impl VaultTreasury {
 
 #[account]
 pub struct VaultBalance {
+    pub version: u8,
     pub owner: Pubkey,
     pub amount: u64,
     pub bump: u8,
 }
 
 impl VaultBalance {
-    pub const LEN: usize = 8 + 32 + 8 + 1;
+    pub const LEN: usize = 8 + 1 + 32 + 8 + 1;
 }
 
-#[account]
+#[account(zero_copy)]
 pub struct WalletState {
-    pub owners: Vec<OwnerShare>,
+    pub version: u8,
+    pub treasury_bump: u8,
+    pub owner_count: u8,
+    pub guardian_count: u8,
+    pub guardian_quorum: u8,
+    pub operator_count: u8,
+    pub quota_count: u8,
+    pub spending_limit_enabled: u8,
+    pub address_book_enabled: u8,
+    pub timelock_enabled: u8,
+    // 0 = nothing scheduled, 1 = scheduled removal, 2 = scheduled replacement.
+    pub scheduled_state: u8,
+    pub recovery_active: u8,
     pub threshold: u16,
-    pub guardians: GuardianSet,
+    pub capacity: WalletCapacity,
     pub session_nonce: u64,
-    pub treasury_bump: u8,
-    pub operator_delegate: Option<Pubkey>,
+    pub guardian_cooldown: u64,
+    pub proposal_index: u64,
+    pub pending_transfer_index: u64,
+    pub scheduled_slot: u64,
+    pub timelock: TransferTimelock,
+    pub scheduled: TransferTimelock,
+    pub active_sessions: u32,
+    // Pending transfer and proposal PDAs not yet closed; the wallet cannot be closed over them.
+    pub open_pending_transfers: u32,
+    pub open_proposals: u32,
+    // Indexed by `ActionClass`; zero falls back to `threshold`.
+    pub action_thresholds: [u16; ACTION_CLASS_COUNT],
+    // Non-zero while an emergency freeze blocks sessions and the operator delegate.
+    pub delegates_frozen: u8,
+    pub session_registry_enabled: u8,
+    // `WindowUnit` for `guardian_cooldown` and `recovery.execute_after`.
+    pub guardian_cooldown_unit: u8,
+    // Always equal to `owner_count`; the roles section is parallel to the owners section.
+    pub owner_role_count: u8,
+    pub _padding: [u8; 2],
+}
+
+// The variable-length parts of a wallet follow the header in this order, each holding as many
+// entries as its count in the header. The 8-byte aligned entries come first, so every section
+// stays aligned and the account only pays rent for what the wallet holds.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WalletSection {
+    Recovery,
+    Operators,
+    OwnerQuotas,
+    Owners,
+    Guardians,
+    OwnerRoles,
+}
+
+impl WalletSection {
+    const ALL: [WalletSection; 6] = [
+        WalletSection::Recovery,
+        WalletSection::Operators,
+        WalletSection::OwnerQuotas,
+        WalletSection::Owners,
+        WalletSection::Guardians,
+        WalletSection::OwnerRoles,
+    ];
+
+    const fn entry_size(self) -> usize {
+        match self {
+            WalletSection::Recovery => std::mem::size_of::<RecoveryProposal>(),
+            WalletSection::Operators => std::mem::size_of::<OperatorEntry>(),
+            WalletSection::OwnerQuotas => std::mem::size_of::<OwnerQuota>(),
+            WalletSection::Owners => std::mem::size_of::<OwnerShare>(),
+            WalletSection::Guardians => std::mem::size_of::<Pubkey>(),
+            WalletSection::OwnerRoles => std::mem::size_of::<u8>(),
+        }
+    }
 }
 
 impl WalletState {
-    pub fn space(max_owners: usize, max_guardians: usize) -> usize {
-        let owners_space = 4 + max_owners * OwnerShare::space();
-        let guardian_space = GuardianSet::base_space(max_guardians)
-            + RecoveryProposal::space(max_owners, max_guardians);
-        8 + owners_space + 2 + guardian_space + 8 + 1 + 1 + 32
+    pub const VERSION: u8 = 1;
+    // The fixed header; the sections follow it.
+    pub const LEN: usize = 8 + std::mem::size_of::<WalletState>();
+    pub const MAX_LEN: usize = Self::LEN
+        + WalletSection::Recovery.entry_size()
+        + MAX_OPERATORS * WalletSection::Operators.entry_size()
+        + MAX_OWNERS * WalletSection::OwnerQuotas.entry_size()
+        + MAX_OWNERS * WalletSection::Owners.entry_size()
+        + MAX_GUARDIANS * WalletSection::Guardians.entry_size()
+        + MAX_OWNERS * WalletSection::OwnerRoles.entry_size();
+
+    // Size of a freshly initialised wallet: no recovery or owner quotas.
+    pub fn initial_space(owners: usize, guardians: usize, operators: usize) -> usize {
+        Self::LEN
+            + operators * WalletSection::Operators.entry_size()
+            + owners * WalletSection::Owners.entry_size()
+            + guardians * WalletSection::Guardians.entry_size()
+            + owners * WalletSection::OwnerRoles.entry_size()
+    }
+
+    // Exact size of the wallet as it stands; accounts are reallocated to this after changes.
+    pub fn current_space(&self) -> usize {
+        Self::LEN + self.section_range(WalletSection::OwnerRoles).end
+    }
+
+    fn section_count(&self, section: WalletSection) -> usize {
+        match section {
+            WalletSection::Recovery => self.recovery_active as usize,
+            WalletSection::Operators => self.operator_count as usize,
+            WalletSection::OwnerQuotas => self.quota_count as usize,
+            WalletSection::Owners => self.owner_count as usize,
+            WalletSection::Guardians => self.guardian_count as usize,
+            WalletSection::OwnerRoles => self.owner_role_count as usize,
+        }
+    }
+
+    fn set_section_count(&mut self, section: WalletSection, count: usize) {
+        let count = count as u8;
+        match section {
+            WalletSection::Recovery => self.recovery_active = count,
+            WalletSection::Operators => self.operator_count = count,
+            WalletSection::OwnerQuotas => self.quota_count = count,
+            WalletSection::Owners => self.owner_count = count,
+            WalletSection::Guardians => self.guardian_count = count,
+            WalletSection::OwnerRoles => self.owner_role_count = count,
+        }
+    }
+
+    // Byte range of `section` within the data after the header.
+    fn section_range(&self, section: WalletSection) -> Range<usize> {
+        let start = WalletSection::ALL[..section as usize]
+            .iter()
+            .map(|previous| self.section_count(*previous) * previous.entry_size())
+            .sum::<usize>();
+        start..start + self.section_count(section) * section.entry_size()
+    }
+
+    pub fn threshold_for(&self, action: ActionClass) -> u16 {
+        match self.action_thresholds[action as usize] {
+            0 => self.threshold,
+            threshold => threshold,
+        }
+    }
+
+    pub fn set_action_threshold(&mut self, action: ActionClass, threshold: Option<u16>) {
+        self.action_thresholds[action as usize] = threshold.unwrap_or_default();
+    }
+
+    pub fn guardian_cooldown_unit(&self) -> WindowUnit {
+        self.guardian_cooldown_unit.into()
+    }
+
+    pub fn transfer_timelock(&self) -> Option<TransferTimelock> {
+        (self.timelock_enabled != 0).then_some(self.timelock)
+    }
+
+    pub fn set_transfer_timelock(&mut self, timelock: Option<TransferTimelock>) {
+        self.timelock_enabled = timelock.is_some() as u8;
+        self.timelock = timelock.unwrap_or_else(TransferTimelock::zeroed);
+    }
+
+    pub fn scheduled_timelock(&self) -> Option<ScheduledTimelock> {
+        let timelock = match self.scheduled_state {
+            0 => return None,
+            1 => None,
+            _ => Some(self.scheduled),
+        };
+        Some(ScheduledTimelock {
+            timelock,
+            effective_slot: self.scheduled_slot,
+        })
+    }
+
+    pub fn set_scheduled_timelock(&mut self, scheduled: Option<ScheduledTimelock>) {
+        self.scheduled_state = match scheduled {
+            None => 0,
+            Some(ScheduledTimelock { timelock: None, .. }) => 1,
+            Some(_) => 2,
+        };
+        self.scheduled = scheduled
+            .and_then(|scheduled| scheduled.timelock)
+            .unwrap_or_else(TransferTimelock::zeroed);
+        self.scheduled_slot = scheduled
+            .map(|scheduled| scheduled.effective_slot)
+            .unwrap_or_default();
+    }
+
+    pub fn effective_transfer_timelock(&self, clock: &Clock) -> Option<TransferTimelock> {
+        match self.scheduled_timelock() {
+            Some(scheduled) if clock.slot >= scheduled.effective_slot => scheduled.timelock,
+            _ => self.transfer_timelock(),
+        }
+    }
+
+    // Zero-copy loads only check the discriminator, so Borsh wallets are told apart by their
+    // version byte, their owner count, and a length matching the header's sections. A v0 wallet
+    // has zeroes where the owner count sits.
+    pub fn is_current(info: &AccountInfo) -> bool {
+        let Ok(data) = info.try_borrow_data() else {
+            return false;
+        };
+        if data.len() < Self::LEN || data.len() > Self::MAX_LEN || data[8] != Self::VERSION {
+            return false;
+        }
+        let wallet = WalletView::new(&data);
+        wallet.owner_count > 0
+            && wallet.owner_role_count == wallet.owner_count
+            && wallet.current_space() == data.len()
+    }
+
+    // Returns the wallet decoded from the Borsh layout that predates zero-copy.
+    pub fn read_legacy(info: &AccountInfo) -> Result<v0::WalletState> {
+        require!(!Self::is_current(info), AlphaError::AccountAlreadyMigrated);
+        let data = info.try_borrow_data()?;
+        require!(
+            data.len() > 8 && data[..8] == Self::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        // v0 layouts predate the version byte; their fixed allocation identifies them.
+        require!(
+            data.len() == v0::WalletState::space(MAX_OWNERS, MAX_GUARDIANS),
+            AlphaError::UnsupportedAccountVersion
+        );
+        let mut slice: &[u8] = &data[8..];
+        v0::WalletState::deserialize(&mut slice)
+            .map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))
+    }
+}
+
+fn split_wallet(data: &[u8]) -> (&WalletState, &[u8]) {
+    let (header, tail) = data[8..].split_at(std::mem::size_of::<WalletState>());
+    (bytemuck::from_bytes(header), tail)
+}
+
+fn split_wallet_mut(data: &mut [u8]) -> (&mut WalletState, &mut [u8]) {
+    let (header, tail) = data[8..].split_at_mut(std::mem::size_of::<WalletState>());
+    (bytemuck::from_bytes_mut(header), tail)
+}
+
+// A wallet account split into its zero-copy header and the sections after it.
+pub struct WalletView<H, T> {
+    header: H,
+    tail: T,
+}
+
+pub type WalletRef<'a> = WalletView<Ref<'a, WalletState>, Ref<'a, [u8]>>;
+pub type WalletRefMut<'a> = WalletView<RefMut<'a, WalletState>, RefMut<'a, [u8]>>;
+
+impl<'a> WalletView<&'a WalletState, &'a [u8]> {
+    // Reads a wallet from raw account data, discriminator included.
+    pub fn new(data: &'a [u8]) -> Self {
+        let (header, tail) = split_wallet(data);
+        Self { header, tail }
+    }
+}
+
+impl<'a> WalletView<&'a mut WalletState, &'a mut [u8]> {
+    pub fn new_mut(data: &'a mut [u8]) -> Self {
+        let (header, tail) = split_wallet_mut(data);
+        Self { header, tail }
+    }
+}
+
+impl<H: Deref<Target = WalletState>, T> Deref for WalletView<H, T> {
+    type Target = WalletState;
+
+    fn deref(&self) -> &WalletState {
+        &self.header
+    }
+}
+
+impl<H: DerefMut<Target = WalletState>, T> DerefMut for WalletView<H, T> {
+    fn deref_mut(&mut self) -> &mut WalletState {
+        &mut self.header
+    }
+}
+
+impl<H: Deref<Target = WalletState>, T: Deref<Target = [u8]>> WalletAccount for WalletView<H, T> {
+    fn tail(&self) -> &[u8] {
+        &self.tail
+    }
+}
+
+impl<H: DerefMut<Target = WalletState>, T: DerefMut<Target = [u8]>> WalletAccountMut
+    for WalletView<H, T>
+{
+    fn tail_mut(&mut self) -> &mut [u8] {
+        &mut self.tail
+    }
+}
+
+pub trait WalletLoader {
+    fn load_wallet(&self) -> Result<WalletRef<'_>>;
+    fn load_wallet_mut(&self) -> Result<WalletRefMut<'_>>;
+    // For an account just created by `init`; the discriminator is written on exit.
+    fn load_wallet_init(&self) -> Result<WalletRefMut<'_>>;
+}
+
+impl<'info> WalletLoader for AccountLoader<'info, WalletState> {
+    fn load_wallet(&self) -> Result<WalletRef<'_>> {
+        drop(self.load()?);
+        let data = self.as_ref().try_borrow_data()?;
+        let (header, tail) = Ref::map_split(data, |data| split_wallet(data));
+        Ok(WalletView { header, tail })
+    }
+
+    fn load_wallet_mut(&self) -> Result<WalletRefMut<'_>> {
+        drop(self.load_mut()?);
+        let data = self.as_ref().try_borrow_mut_data()?;
+        let (header, tail) = RefMut::map_split(data, |data| split_wallet_mut(data));
+        Ok(WalletView { header, tail })
+    }
+
+    fn load_wallet_init(&self) -> Result<WalletRefMut<'_>> {
+        drop(self.load_init()?);
+        let data = self.as_ref().try_borrow_mut_data()?;
+        let (header, tail) = RefMut::map_split(data, |data| split_wallet_mut(data));
+        Ok(WalletView { header, tail })
+    }
+}
+
+fn section<W: WalletAccount + ?Sized, E: Pod>(wallet: &W, section: WalletSection) -> &[E] {
+    bytemuck::cast_slice(&wallet.tail()[wallet.section_range(section)])
+}
+
+fn section_mut<W: WalletAccountMut + ?Sized, E: Pod>(
+    wallet: &mut W,
+    section: WalletSection,
+) -> &mut [E] {
+    let range = wallet.section_range(section);
+    bytemuck::cast_slice_mut(&mut wallet.tail_mut()[range])
+}
+
+// Sets how many entries `section` holds, shifting the sections after it. New entries are zeroed.
+// The account must already be large enough; handlers reserve `WalletState::MAX_LEN` first.
+fn resize_section<W: WalletAccountMut + ?Sized>(
+    wallet: &mut W,
+    section: WalletSection,
+    count: usize,
+) {
+    let range = wallet.section_range(section);
+    let used = wallet.current_space() - WalletState::LEN;
+    let end = range.start + count * section.entry_size();
+    let tail = wallet.tail_mut();
+    tail.copy_within(range.end..used, end);
+    if end > range.end {
+        tail[range.end..end].fill(0);
+    } else {
+        tail[used - (range.end - end)..used].fill(0);
+    }
+    wallet.set_section_count(section, count);
+}
+
+// Removes the entry at `index` from `section`, keeping the rest in order.
+fn remove_entry<W: WalletAccountMut + ?Sized>(
+    wallet: &mut W,
+    section: WalletSection,
+    index: usize,
+) {
+    let range = wallet.section_range(section);
+    let size = section.entry_size();
+    wallet.tail_mut().copy_within(
+        range.start + (index + 1) * size..range.end,
+        range.start + index * size,
+    );
+    let count = wallet.section_count(section);
+    resize_section(wallet, section, count - 1);
+}
+
+pub trait WalletAccount: Deref<Target = WalletState> {
+    fn tail(&self) -> &[u8];
+
+    fn owners(&self) -> &[OwnerShare] {
+        section(self, WalletSection::Owners)
+    }
+
+    fn guardians(&self) -> &[Pubkey] {
+        section(self, WalletSection::Guardians)
+    }
+
+    fn operators(&self) -> &[OperatorEntry] {
+        section(self, WalletSection::Operators)
+    }
+
+    fn owner_quotas(&self) -> &[OwnerQuota] {
+        section(self, WalletSection::OwnerQuotas)
+    }
+
+    fn owner_roles(&self) -> &[u8] {
+        section(self, WalletSection::OwnerRoles)
+    }
+
+    fn active_recovery(&self) -> Option<&RecoveryProposal> {
+        section(self, WalletSection::Recovery).first()
     }
 
-    pub fn owner_weight(&self, owner: &Pubkey) -> Option<u16> {
-        self.owners
+    fn owner_weight(&self, owner: &Pubkey) -> Option<u16> {
+        self.owners()
             .iter()
             .find(|entry| entry.owner == *owner)
             .map(|entry| entry.weight)
     }
+
+    fn owner_index(&self, owner: &Pubkey) -> Option<usize> {
+        self.owners().iter().position(|entry| entry.owner == *owner)
+    }
+
+    fn operator(&self, operator: &Pubkey) -> Option<&OperatorEntry> {
+        self.operators()
+            .iter()
+            .find(|entry| entry.operator == *operator)
+    }
+
+    fn total_weight(&self) -> u64 {
+        self.owners().iter().map(|entry| entry.weight as u64).sum()
+    }
+
+    fn weight_of(&self, voters: &[Pubkey]) -> u64 {
+        voters
+            .iter()
+            .filter_map(|voter| self.owner_weight(voter))
+            .map(|weight| weight as u64)
+            .sum()
+    }
+
+    fn owner_has_role(&self, owner: &Pubkey, role: u8) -> bool {
+        self.owner_index(owner)
+            .map(|index| self.owner_roles()[index] & role == role)
+            .unwrap_or(false)
+    }
+
+    fn can_cancel(&self, key: &Pubkey) -> bool {
+        self.guardians().contains(key) || self.owner_has_role(key, OWNER_ROLE_CANCELLER)
+    }
+
+    fn recovery_deadline(&self) -> Option<Deadline> {
+        self.active_recovery().map(|recovery| {
+            self.guardian_cooldown_unit()
+                .deadline(recovery.execute_after)
+        })
+    }
+}
+
+pub trait WalletAccountMut: WalletAccount + DerefMut {
+    fn tail_mut(&mut self) -> &mut [u8];
+
+    fn operator_mut(&mut self, operator: &Pubkey) -> Option<&mut OperatorEntry> {
+        section_mut::<_, OperatorEntry>(self, WalletSection::Operators)
+            .iter_mut()
+            .find(|entry| entry.operator == *operator)
+    }
+
+    fn push_operator(&mut self, entry: OperatorEntry) -> Result<()> {
+        let count = self.operator_count as usize;
+        require!(count < MAX_OPERATORS, AlphaError::TooManyOperators);
+        resize_section(self, WalletSection::Operators, count + 1);
+        section_mut(self, WalletSection::Operators)[count] = entry;
+        Ok(())
+    }
+
+    fn remove_operator(&mut self, operator: &Pubkey) {
+        if let Some(index) = self
+            .operators()
+            .iter()
+            .position(|entry| entry.operator == *operator)
+        {
+            remove_entry(self, WalletSection::Operators, index);
+        }
+    }
+
+    fn owner_quota_mut(&mut self, owner: &Pubkey) -> Option<&mut OwnerQuota> {
+        section_mut::<_, OwnerQuota>(self, WalletSection::OwnerQuotas)
+            .iter_mut()
+            .find(|entry| entry.owner == *owner)
+    }
+
+    fn set_owner_quota(&mut self, quota: OwnerQuota) {
+        match self.owner_quota_mut(&quota.owner) {
+            Some(existing) => *existing = quota,
+            None => {
+                // Quotas are per owner, so the section never outgrows `MAX_OWNERS`.
+                let count = self.quota_count as usize;
+                resize_section(self, WalletSection::OwnerQuotas, count + 1);
+                section_mut(self, WalletSection::OwnerQuotas)[count] = quota;
+            }
+        }
+    }
+
+    fn remove_owner_quota(&mut self, owner: &Pubkey) {
+        if let Some(index) = self
+            .owner_quotas()
+            .iter()
+            .position(|entry| entry.owner == *owner)
+        {
+            remove_entry(self, WalletSection::OwnerQuotas, index);
+        }
+    }
+
+    fn replace_owners(&mut self, owners: &[OwnerShare], threshold: u16) {
+        let roles: Vec<u8> = owners
+            .iter()
+            .map(|entry| {
+                self.owner_index(&entry.owner)
+                    .map(|previous| self.owner_roles()[previous])
+                    .unwrap_or(OWNER_ROLE_ALL)
+            })
+            .collect();
+        resize_section(self, WalletSection::Owners, owners.len());
+        section_mut(self, WalletSection::Owners).copy_from_slice(owners);
+        resize_section(self, WalletSection::OwnerRoles, owners.len());
+        section_mut(self, WalletSection::OwnerRoles).copy_from_slice(&roles);
+        self.threshold = threshold;
+
+        let mut index = 0;
+        while index < self.owner_quotas().len() {
+            if self
+                .owner_weight(&self.owner_quotas()[index].owner)
+                .is_some()
+            {
+                index += 1;
+            } else {
+                remove_entry(self, WalletSection::OwnerQuotas, index);
+            }
+        }
+
+        let total_weight = self.total_weight();
+        for threshold in self.action_thresholds.iter_mut() {
+            *threshold = (*threshold as u64).min(total_weight) as u16;
+        }
+    }
+
+    fn set_guardians(
+        &mut self,
+        guardians: &[Pubkey],
+        quorum: u8,
+        cooldown: u64,
+        cooldown_unit: WindowUnit,
+    ) {
+        resize_section(self, WalletSection::Guardians, guardians.len());
+        section_mut(self, WalletSection::Guardians).copy_from_slice(guardians);
+        self.guardian_quorum = quorum;
+        self.guardian_cooldown = cooldown;
+        self.guardian_cooldown_unit = cooldown_unit.into();
+    }
+
+    fn set_owner_roles(&mut self, owner: &Pubkey, roles: u8) {
+        if let Some(index) = self.owner_index(owner) {
+            section_mut::<_, u8>(self, WalletSection::OwnerRoles)[index] = roles;
+        }
+    }
+
+    fn active_recovery_mut(&mut self) -> Option<&mut RecoveryProposal> {
+        section_mut(self, WalletSection::Recovery).first_mut()
+    }
+
+    fn set_recovery(&mut self, recovery: Option<RecoveryProposal>) {
+        resize_section(self, WalletSection::Recovery, recovery.is_some() as usize);
+        if let Some(recovery) = recovery {
+            section_mut(self, WalletSection::Recovery)[0] = recovery;
+        }
+    }
+
+    // Fills a zeroed wallet from the deployed v0 layout.
+    fn upgrade_v0(&mut self, legacy: v0::WalletState) -> Result<()> {
+        let recovery_owners = legacy
+            .guardians
+            .active_recovery
+            .as_ref()
+            .map(|recovery| recovery.new_owners.len())
+            .unwrap_or_default();
+        require!(
+            legacy.owners.len().max(recovery_owners) <= MAX_OWNERS,
+            AlphaError::TooManyOwners
+        );
+
+        self.version = WalletState::VERSION;
+        self.treasury_bump = legacy.treasury_bump;
+        self.replace_owners(&legacy.owners, legacy.threshold);
+        self.set_guardians(
+            &legacy.guardians.guardians,
+            legacy.guardians.quorum,
+            legacy.guardians.cooldown_slots,
+            WindowUnit::Slots,
+        );
+        self.set_recovery(legacy.guardians.active_recovery.map(|recovery| {
+            RecoveryProposal::new(
+                recovery.proposed_slot,
+                recovery.execute_after_slot,
+                recovery.new_threshold,
+                &recovery.new_owners,
+                &recovery.approvals,
+            )
+        }));
+        self.session_nonce = legacy.session_nonce;
+        if let Some(operator) = legacy.operator_delegate {
+            self.push_operator(OperatorEntry::new(operator, OPERATOR_SCOPE_ALL, None, None))?;
+        }
+        self.capacity = WalletCapacity::default();
+        Ok(())
+    }
 }
 
 #[derive(
@@ -152,31 +919,1048 @@ impl WalletState {
     JsonSchema,
 )]
 pub struct SessionConfig {
-    pub expires_at_slot: Option<u64>,
+    pub expires_at: Option<Deadline>,
     pub usage_limit: Option<u64>,
     pub value_limit: Option<u64>,
     pub allowed_programs: Vec<Pubkey>,
+    pub token_allowances: Vec<TokenAllowance>,
+    pub rate_limit: Option<SessionRateLimitConfig>,
+    // Empty lists leave destinations and instructions unrestricted beyond `allowed_programs`.
+    pub allowed_destinations: Vec<Pubkey>,
+    pub instruction_templates: Vec<InstructionTemplate>,
 }
 
 impl SessionConfig {
-    pub fn space(max_allowed_programs: usize) -> usize {
-        1 + 8 + 1 + 8 + 1 + 8 + 4 + max_allowed_programs * 32
+    pub fn space(max_allowed_programs: usize, max_token_allowances: usize) -> usize {
+        1 + Deadline::space()
+            + 1
+            + 8
+            + 1
+            + 8
+            + 4
+            + max_allowed_programs * 32
+            + 4
+            + max_token_allowances * TokenAllowance::space()
+            + 1
+            + SessionRateLimitConfig::space()
+            + (4 + MAX_SESSION_DESTINATIONS * 32)
+            + (4 + MAX_SESSION_TEMPLATES * InstructionTemplate::space())
     }
 }
 
-#[account]
-pub struct SessionKeyAccount {
-    pub wallet: Pubkey,
-    pub authority: Pubkey,
-    pub expires_at_slot: Option<u64>,
-    pub remaining_calls: Option<u64>,
-    pub remaining_value: Option<u64>,
-    pub allowed_programs: Vec<Pubkey>,
-    pub bump: u8,
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct TemplateAccount {
+    // Position in the instruction's account list, not in `remaining_accounts`.
+    pub position: u8,
+    pub pubkey: Pubkey,
 }
 
-impl SessionKeyAccount {
-    pub fn space(max_programs: usize) -> usize {
-        8 + 32 + 32 + 1 + 8 + 1 + 8 + 1 + 8 + (4 + max_programs * 32) + 1
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct InstructionTemplate {
+    pub program_id: Pubkey,
+    pub discriminator: Vec<u8>,
+    pub fixed_accounts: Vec<TemplateAccount>,
+}
+
+impl InstructionTemplate {
+    pub fn space() -> usize {
+        32 + (4 + MAX_TEMPLATE_DISCRIMINATOR_LENGTH) + (4 + MAX_TEMPLATE_FIXED_ACCOUNTS * (1 + 32))
+    }
+
+    // `accounts` are the instruction's account keys in order.
+    pub fn matches(&self, program_id: &Pubkey, data: &[u8], accounts: &[Pubkey]) -> bool {
+        self.program_id == *program_id
+            && data.starts_with(&self.discriminator)
+            && self
+                .fixed_accounts
+                .iter()
+                .all(|fixed| accounts.get(fixed.position as usize) == Some(&fixed.pubkey))
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct SessionRateLimitConfig {
+    pub period_length: u64,
+    pub period_unit: WindowUnit,
+    pub calls_per_period: Option<u64>,
+    pub value_per_period: Option<u64>,
+}
+
+impl SessionRateLimitConfig {
+    pub fn space() -> usize {
+        8 + 1 + (1 + 8) + (1 + 8)
+    }
+}
+
+// Refilling budget checked alongside the session's lifetime `remaining_*` caps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionRateLimit {
+    pub period_length: u64,
+    pub period_unit: WindowUnit,
+    pub calls_per_period: Option<u64>,
+    pub value_per_period: Option<u64>,
+    pub period_start: u64,
+    pub calls_in_period: u64,
+    pub spent_in_period: u64,
+}
+
+impl SessionRateLimit {
+    pub fn from_config(config: SessionRateLimitConfig, clock: &Clock) -> Self {
+        Self {
+            period_length: config.period_length,
+            period_unit: config.period_unit,
+            calls_per_period: config.calls_per_period,
+            value_per_period: config.value_per_period,
+            period_start: config.period_unit.now(clock),
+            calls_in_period: 0,
+            spent_in_period: 0,
+        }
+    }
+
+    pub fn space() -> usize {
+        SessionRateLimitConfig::space() + 8 + 8 + 8
+    }
+
+    pub fn roll_period(&mut self, clock: &Clock) {
+        if self
+            .period_unit
+            .roll(clock, &mut self.period_start, self.period_length)
+        {
+            self.calls_in_period = 0;
+            self.spent_in_period = 0;
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct TokenAllowance {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+impl TokenAllowance {
+    pub fn space() -> usize {
+        32 + 8
+    }
+}
+
+#[account]
+pub struct SessionKeyAccount {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    // The wallet's `session_nonce` when the session was created; it stops working once that moves on.
+    pub epoch: u64,
+    pub expires_at: Option<Deadline>,
+    pub remaining_calls: Option<u64>,
+    pub remaining_value: Option<u64>,
+    pub allowed_programs: Vec<Pubkey>,
+    pub bump: u8,
+    pub token_allowances: Vec<TokenAllowance>,
+    pub rate_limit: Option<SessionRateLimit>,
+    pub allowed_destinations: Vec<Pubkey>,
+    pub instruction_templates: Vec<InstructionTemplate>,
+}
+
+impl SessionKeyAccount {
+    pub fn space(max_programs: usize, max_token_allowances: usize) -> usize {
+        8 + 1
+            + 32
+            + 32
+            + 8
+            + (1 + Deadline::space())
+            + 1
+            + 8
+            + 1
+            + 8
+            + (4 + max_programs * 32)
+            + 1
+            + (4 + max_token_allowances * TokenAllowance::space())
+            + (1 + SessionRateLimit::space())
+            + (4 + MAX_SESSION_DESTINATIONS * 32)
+            + (4 + MAX_SESSION_TEMPLATES * InstructionTemplate::space())
+    }
+
+    pub fn allows_destination(&self, candidates: &[Pubkey]) -> bool {
+        self.allowed_destinations.is_empty()
+            || candidates
+                .iter()
+                .any(|candidate| self.allowed_destinations.contains(candidate))
+    }
+
+    pub fn allows_instruction(
+        &self,
+        program_id: &Pubkey,
+        data: &[u8],
+        accounts: &[Pubkey],
+    ) -> bool {
+        self.instruction_templates.is_empty()
+            || self
+                .instruction_templates
+                .iter()
+                .any(|template| template.matches(program_id, data, accounts))
+    }
+
+    pub fn token_allowance_mut(&mut self, mint: &Pubkey) -> Option<&mut TokenAllowance> {
+        self.token_allowances
+            .iter_mut()
+            .find(|allowance| allowance.mint == *mint)
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct SessionRegistryEntry {
+    pub authority: Pubkey,
+    pub epoch: u64,
+    pub expires_at: Option<Deadline>,
+}
+
+impl SessionRegistryEntry {
+    pub fn space() -> usize {
+        32 + 8 + (1 + Deadline::space())
+    }
+}
+
+impl From<&SessionKeyAccount> for SessionRegistryEntry {
+    fn from(session: &SessionKeyAccount) -> Self {
+        Self {
+            authority: session.authority,
+            epoch: session.epoch,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+// One entry per open session account of the wallet, including sessions from older epochs.
+#[account]
+pub struct SessionRegistry {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub entries: Vec<SessionRegistryEntry>,
+    pub bump: u8,
+}
+
+impl SessionRegistry {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = 8 + 1 + 32 + (4 + MAX_REGISTERED_SESSIONS * (32 + 8 + 1 + 1 + 8)) + 1;
+
+    pub fn upsert(&mut self, session: &SessionKeyAccount) -> Result<()> {
+        let entry = SessionRegistryEntry::from(session);
+        if let Some(existing) = self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.authority == entry.authority)
+        {
+            *existing = entry;
+            return Ok(());
+        }
+        require!(
+            self.entries.len() < MAX_REGISTERED_SESSIONS,
+            AlphaError::SessionRegistryFull
+        );
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, authority: &Pubkey) {
+        self.entries.retain(|entry| entry.authority != *authority);
+    }
+}
+
+#[account]
+pub struct SessionRequest {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub authority: Pubkey,
+    pub payer: Pubkey,
+    pub config: SessionConfig,
+    pub requested_slot: u64,
+    pub bump: u8,
+}
+
+impl SessionRequest {
+    pub const VERSION: u8 = 1;
+
+    pub fn space() -> usize {
+        8 + 1
+            + 32
+            + 32
+            + 32
+            + SessionConfig::space(MAX_SESSION_PROGRAMS, MAX_SESSION_TOKEN_ALLOWANCES)
+            + 8
+            + 1
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct WalletInstruction {
+    pub program_id: Pubkey,
+    pub account_indexes: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl WalletInstruction {
+    pub fn space() -> usize {
+        32 + (4 + MAX_INSTRUCTION_ACCOUNTS) + (4 + MAX_INSTRUCTION_DATA_LENGTH)
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum ProposalAction {
+    Transfer {
+        destination: Pubkey,
+        amount: u64,
+        memo: Option<Vec<u8>>,
+    },
+    ConfigureOperator {
+        operator: Pubkey,
+        config: Option<OperatorConfig>,
+    },
+    UpdateOwners {
+        owners: Vec<OwnerShare>,
+        threshold: u16,
+    },
+    RegisterSession {
+        authority: Pubkey,
+        config: SessionConfig,
+    },
+    ExecuteInstructions {
+        instructions: Vec<WalletInstruction>,
+    },
+}
+
+impl ProposalAction {
+    pub fn class(&self) -> ActionClass {
+        match self {
+            ProposalAction::Transfer { .. } => ActionClass::Transfer,
+            ProposalAction::ConfigureOperator { .. } => ActionClass::ConfigureOperator,
+            ProposalAction::UpdateOwners { .. } => ActionClass::UpdateOwners,
+            ProposalAction::RegisterSession { .. } => ActionClass::RegisterSession,
+            ProposalAction::ExecuteInstructions { .. } => ActionClass::ExecuteInstructions,
+        }
+    }
+
+    pub fn space(max_owners: usize, max_session_programs: usize) -> usize {
+        let transfer = 32 + 8 + 1 + 4 + MAX_MEMO_LENGTH;
+        let configure_operator = 32 + 1 + OperatorConfig::space(MAX_OPERATOR_DESTINATIONS);
+        let update_owners = 4 + max_owners * OwnerShare::space() + 2;
+        let register_session =
+            32 + SessionConfig::space(max_session_programs, MAX_SESSION_TOKEN_ALLOWANCES);
+        let execute_instructions = 4 + MAX_WALLET_INSTRUCTIONS * WalletInstruction::space();
+        1 + transfer
+            .max(configure_operator)
+            .max(update_owners)
+            .max(register_session)
+            .max(execute_instructions)
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum ProposalStatus {
+    Active,
+    Rejected,
+    Executed,
+}
+
+#[account]
+pub struct Proposal {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub action: ProposalAction,
+    pub status: ProposalStatus,
+    pub created_slot: u64,
+    pub expires_at_slot: u64,
+    pub approvals: Vec<Pubkey>,
+    pub rejections: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl Proposal {
+    pub const VERSION: u8 = 1;
+
+    pub fn space(max_owners: usize, max_session_programs: usize) -> usize {
+        8 + 1
+            + 32
+            + 8
+            + 32
+            + ProposalAction::space(max_owners, max_session_programs)
+            + 1
+            + 8
+            + 8
+            // Votes are bounded by `MAX_OWNERS` rather than the wallet's capacity, which can be
+            // raised while the proposal is open.
+            + (4 + MAX_OWNERS * 32)
+            + (4 + MAX_OWNERS * 32)
+            + 1
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum WindowUnit {
+    Slots,
+    Seconds,
+}
+
+impl WindowUnit {
+    pub fn now(&self, clock: &Clock) -> u64 {
+        match self {
+            WindowUnit::Slots => clock.slot,
+            WindowUnit::Seconds => clock.unix_timestamp.max(0) as u64,
+        }
+    }
+
+    // Restarts a window at `now` once `length` has elapsed since `start`; true when it rolled over.
+    pub fn roll(&self, clock: &Clock, start: &mut u64, length: u64) -> bool {
+        let now = self.now(clock);
+        if now.saturating_sub(*start) < length {
+            return false;
+        }
+        *start = now;
+        true
+    }
+
+    pub fn deadline(&self, value: u64) -> Deadline {
+        match self {
+            WindowUnit::Slots => Deadline::Slot(value),
+            WindowUnit::Seconds => Deadline::Timestamp(value as i64),
+        }
+    }
+}
+
+impl From<WindowUnit> for u8 {
+    fn from(unit: WindowUnit) -> Self {
+        unit as u8
+    }
+}
+
+impl From<u8> for WindowUnit {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => WindowUnit::Seconds,
+            _ => WindowUnit::Slots,
+        }
+    }
+}
+
+#[account]
+pub struct SpendingLimit {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub max_outflow: u64,
+    pub window_length: u64,
+    pub window_unit: WindowUnit,
+    pub elevated_threshold: u16,
+    pub window_start: u64,
+    pub spent_in_window: u64,
+    pub bump: u8,
+}
+
+impl SpendingLimit {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = 8 + 1 + 32 + 8 + 8 + 1 + 2 + 8 + 8 + 1;
+
+    pub fn roll_window(&mut self, clock: &Clock) {
+        if self
+            .window_unit
+            .roll(clock, &mut self.window_start, self.window_length)
+        {
+            self.spent_in_window = 0;
+        }
+    }
+
+    pub fn exceeds(&self, amount: u64) -> bool {
+        self.spent_in_window.saturating_add(amount) > self.max_outflow
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct OperatorPolicyConfig {
+    pub period_allowance: u64,
+    pub period_length: u64,
+    pub period_unit: WindowUnit,
+    pub max_single_transfer: u64,
+    pub allowed_destinations: Vec<Pubkey>,
+}
+
+impl OperatorPolicyConfig {
+    pub fn space(max_destinations: usize) -> usize {
+        8 + 8 + 1 + 8 + (4 + max_destinations * 32)
+    }
+}
+
+#[zero_copy]
+pub struct OperatorPolicy {
+    pub period_allowance: u64,
+    pub period_length: u64,
+    pub max_single_transfer: u64,
+    pub period_start: u64,
+    pub spent_in_period: u64,
+    pub allowed_destinations: [Pubkey; MAX_OPERATOR_DESTINATIONS],
+    pub destination_count: u8,
+    pub period_unit: u8,
+    pub _padding: [u8; 6],
+}
+
+impl OperatorPolicy {
+    pub fn from_config(config: OperatorPolicyConfig, clock: &Clock) -> Self {
+        let mut policy = Self::zeroed();
+        policy.period_allowance = config.period_allowance;
+        policy.period_length = config.period_length;
+        policy.period_unit = config.period_unit.into();
+        policy.max_single_transfer = config.max_single_transfer;
+        policy.set_allowed_destinations(&config.allowed_destinations);
+        policy.period_start = config.period_unit.now(clock);
+        policy
+    }
+
+    pub fn period_unit(&self) -> WindowUnit {
+        self.period_unit.into()
+    }
+
+    pub fn allowed_destinations(&self) -> &[Pubkey] {
+        &self.allowed_destinations[..self.destination_count as usize]
+    }
+
+    fn set_allowed_destinations(&mut self, destinations: &[Pubkey]) {
+        self.allowed_destinations[..destinations.len()].copy_from_slice(destinations);
+        self.destination_count = destinations.len() as u8;
+    }
+
+    pub fn roll_period(&mut self, clock: &Clock) {
+        if self
+            .period_unit()
+            .roll(clock, &mut self.period_start, self.period_length)
+        {
+            self.spent_in_period = 0;
+        }
+    }
+
+    pub fn allows_destination(&self, candidates: &[Pubkey]) -> bool {
+        self.allowed_destinations().is_empty()
+            || candidates
+                .iter()
+                .any(|candidate| self.allowed_destinations().contains(candidate))
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum Deadline {
+    Slot(u64),
+    Timestamp(i64),
+}
+
+impl Deadline {
+    pub fn space() -> usize {
+        1 + 8
+    }
+
+    pub fn has_passed(&self, clock: &Clock) -> bool {
+        match self {
+            Deadline::Slot(slot) => clock.slot > *slot,
+            Deadline::Timestamp(timestamp) => clock.unix_timestamp > *timestamp,
+        }
+    }
+
+    pub fn is_reached(&self, clock: &Clock) -> bool {
+        match self {
+            Deadline::Slot(slot) => clock.slot >= *slot,
+            Deadline::Timestamp(timestamp) => clock.unix_timestamp >= *timestamp,
+        }
+    }
+
+    pub fn slot(&self) -> Option<u64> {
+        match self {
+            Deadline::Slot(slot) => Some(*slot),
+            Deadline::Timestamp(_) => None,
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<i64> {
+        match self {
+            Deadline::Slot(_) => None,
+            Deadline::Timestamp(timestamp) => Some(*timestamp),
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct OperatorConfig {
+    pub scopes: u8,
+    pub expires_at: Option<Deadline>,
+    pub policy: Option<OperatorPolicyConfig>,
+}
+
+impl OperatorConfig {
+    pub fn space(max_destinations: usize) -> usize {
+        1 + (1 + Deadline::space()) + (1 + OperatorPolicyConfig::space(max_destinations))
+    }
+}
+
+#[zero_copy]
+pub struct OperatorEntry {
+    pub operator: Pubkey,
+    pub scopes: u8,
+    // 0 = no expiry, 1 = slot, 2 = unix timestamp.
+    pub expiry_kind: u8,
+    pub has_policy: u8,
+    pub _padding: [u8; 5],
+    pub expiry: u64,
+    pub policy: OperatorPolicy,
+}
+
+impl OperatorEntry {
+    pub fn new(
+        operator: Pubkey,
+        scopes: u8,
+        expires_at: Option<Deadline>,
+        policy: Option<OperatorPolicy>,
+    ) -> Self {
+        let mut entry = Self::zeroed();
+        entry.operator = operator;
+        entry.scopes = scopes;
+        (entry.expiry_kind, entry.expiry) = match expires_at {
+            None => (0, 0),
+            Some(Deadline::Slot(slot)) => (1, slot),
+            Some(Deadline::Timestamp(timestamp)) => (2, timestamp as u64),
+        };
+        entry.has_policy = policy.is_some() as u8;
+        entry.policy = policy.unwrap_or_else(OperatorPolicy::zeroed);
+        entry
+    }
+
+    pub fn unrestricted(operator: Pubkey) -> Self {
+        Self::new(operator, OPERATOR_SCOPE_ALL, None, None)
+    }
+
+    pub fn from_config(operator: Pubkey, config: OperatorConfig, clock: &Clock) -> Self {
+        Self::new(
+            operator,
+            config.scopes,
+            config.expires_at,
+            config
+                .policy
+                .map(|policy| OperatorPolicy::from_config(policy, clock)),
+        )
+    }
+
+    pub fn expires_at(&self) -> Option<Deadline> {
+        match self.expiry_kind {
+            0 => None,
+            1 => Some(Deadline::Slot(self.expiry)),
+            _ => Some(Deadline::Timestamp(self.expiry as i64)),
+        }
+    }
+
+    pub fn policy(&self) -> Option<&OperatorPolicy> {
+        (self.has_policy != 0).then_some(&self.policy)
+    }
+
+    pub fn policy_mut(&mut self) -> Option<&mut OperatorPolicy> {
+        if self.has_policy == 0 {
+            return None;
+        }
+        Some(&mut self.policy)
+    }
+}
+
+#[account]
+pub struct AddressBook {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub entries: Vec<Pubkey>,
+    pub min_delayed_amount: u64,
+    pub delay_slots: u64,
+    pub bump: u8,
+}
+
+impl AddressBook {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = 8 + 1 + 32 + (4 + MAX_ADDRESS_BOOK_ENTRIES * 32) + 8 + 8 + 1;
+
+    pub fn contains(&self, address: &Pubkey) -> bool {
+        self.entries.contains(address)
+    }
+
+    pub fn requires_delay(&self, destination: &Pubkey, amount: u64) -> bool {
+        amount >= self.min_delayed_amount && !self.contains(destination)
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum PendingTransferReason {
+    UnknownDestination,
+    HighValue,
+}
+
+#[repr(C)]
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Pod,
+    Zeroable,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct TransferTimelock {
+    pub min_amount: u64,
+    pub delay_slots: u64,
+}
+
+impl TransferTimelock {
+    pub fn space() -> usize {
+        8 + 8
+    }
+
+    pub fn weakened_by(&self, update: Option<&TransferTimelock>) -> bool {
+        match update {
+            Some(update) => {
+                update.min_amount > self.min_amount || update.delay_slots < self.delay_slots
+            }
+            None => true,
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct ScheduledTimelock {
+    pub timelock: Option<TransferTimelock>,
+    pub effective_slot: u64,
+}
+
+impl ScheduledTimelock {
+    pub fn space() -> usize {
+        (1 + TransferTimelock::space()) + 8
+    }
+}
+
+#[account]
+pub struct PendingTransfer {
+    pub version: u8,
+    pub wallet: Pubkey,
+    pub index: u64,
+    pub requested_by: Pubkey,
+    pub payer: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub memo: Option<Vec<u8>>,
+    pub via_session: bool,
+    pub reason: PendingTransferReason,
+    pub created_slot: u64,
+    pub release_slot: u64,
+    pub bump: u8,
+}
+
+impl PendingTransfer {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize =
+        8 + 1 + 32 + 8 + 32 + 32 + 32 + 8 + (1 + 4 + MAX_MEMO_LENGTH) + 1 + 1 + 8 + 8 + 1;
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct OwnerQuotaConfig {
+    pub allowance: u64,
+    pub period_length: u64,
+    pub period_unit: WindowUnit,
+}
+
+#[zero_copy]
+pub struct OwnerQuota {
+    pub owner: Pubkey,
+    pub allowance: u64,
+    pub period_length: u64,
+    pub period_start: u64,
+    pub spent_in_period: u64,
+    pub period_unit: u8,
+    pub _padding: [u8; 7],
+}
+
+impl OwnerQuota {
+    pub fn from_config(owner: Pubkey, config: OwnerQuotaConfig, clock: &Clock) -> Self {
+        let mut quota = Self::zeroed();
+        quota.owner = owner;
+        quota.allowance = config.allowance;
+        quota.period_length = config.period_length;
+        quota.period_unit = config.period_unit.into();
+        quota.period_start = config.period_unit.now(clock);
+        quota
+    }
+
+    pub fn period_unit(&self) -> WindowUnit {
+        self.period_unit.into()
+    }
+
+    pub fn roll_period(&mut self, clock: &Clock) {
+        if self
+            .period_unit()
+            .roll(clock, &mut self.period_start, self.period_length)
+        {
+            self.spent_in_period = 0;
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum ActionClass {
+    Transfer,
+    ConfigureOperator,
+    RegisterSession,
+    RevokeSession,
+    UpdateOwners,
+    ExecuteInstructions,
+    UpdatePolicy,
+}
+
+impl VersionedAccount for VaultConfig {
+    const VERSION: u8 = 1;
+    type V0 = v0::VaultConfig;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn v0_len() -> usize {
+        v0::VaultConfig::LEN
+    }
+
+    fn upgrade_v0(legacy: v0::VaultConfig) -> Self {
+        Self {
+            version: Self::VERSION,
+            admin: legacy.admin,
+            operator: legacy.operator,
+            bump: legacy.bump,
+        }
+    }
+}
+
+impl VersionedAccount for VaultBalance {
+    const VERSION: u8 = 1;
+    type V0 = v0::VaultBalance;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn v0_len() -> usize {
+        v0::VaultBalance::LEN
+    }
+
+    fn upgrade_v0(legacy: v0::VaultBalance) -> Self {
+        Self {
+            version: Self::VERSION,
+            owner: legacy.owner,
+            amount: legacy.amount,
+            bump: legacy.bump,
+        }
+    }
+}
+
+impl VersionedAccount for SessionKeyAccount {
+    const VERSION: u8 = 1;
+    type V0 = v0::SessionKeyAccount;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn v0_len() -> usize {
+        v0::SessionKeyAccount::space(MAX_SESSION_PROGRAMS)
+    }
+
+    // Legacy sessions get epoch 0 here; `migrate_session_key` stamps the wallet's current epoch.
+    fn upgrade_v0(legacy: v0::SessionKeyAccount) -> Self {
+        Self {
+            version: Self::VERSION,
+            wallet: legacy.wallet,
+            authority: legacy.authority,
+            epoch: 0,
+            expires_at: legacy.expires_at_slot.map(Deadline::Slot),
+            remaining_calls: legacy.remaining_calls,
+            remaining_value: legacy.remaining_value,
+            allowed_programs: legacy.allowed_programs,
+            bump: legacy.bump,
+            token_allowances: Vec::new(),
+            rate_limit: None,
+            allowed_destinations: Vec::new(),
+            instruction_templates: Vec::new(),
+        }
     }
 }
@@ -14,6 +14,23 @@ pub async fn setup_program_test() -> ProgramTestContext {
     program_test.start_with_context().await
 }
 
+// Loads the built `alpha_builder.so` instead of the native entrypoint, so compute units are
+// metered. Returns `None` when no SBF build is available.
+pub async fn setup_sbf_program_test() -> Option<ProgramTestContext> {
+    let out_dir = std::env::var("BPF_OUT_DIR")
+        .or_else(|_| std::env::var("SBF_OUT_DIR"))
+        .ok()?;
+    if !std::path::Path::new(&out_dir)
+        .join("alpha_builder.so")
+        .exists()
+    {
+        return None;
+    }
+    let mut program_test = ProgramTest::new("alpha_builder", alpha_builder::ID, None);
+    program_test.prefer_bpf(true);
+    Some(program_test.start_with_context().await)
+}
+
 pub async fn process_instruction(
     ctx: &mut ProgramTestContext,
     instruction: Instruction,
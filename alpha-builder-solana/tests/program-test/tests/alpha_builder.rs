@@ -1,12 +1,254 @@
+use alpha_builder::errors::AlphaError;
 use alpha_builder::instruction as program_ix;
-use alpha_builder::state::{OwnerShare, SessionConfig, SessionKeyAccount, VaultBalance};
-use alpha_builder_program_test::utils::{process_instruction, setup_program_test};
+use alpha_builder::state::constants::{
+    MAX_GUARDIANS, MAX_OWNERS, MAX_SESSION_PROGRAMS, OPERATOR_SCOPE_ALL, OPERATOR_SCOPE_TRANSFER,
+    OWNER_ROLE_ALL, OWNER_ROLE_CANCELLER,
+};
+use alpha_builder::state::{
+    v0, ActionClass, Deadline, InstructionTemplate, OperatorConfig, OperatorPolicyConfig,
+    OwnerQuotaConfig, OwnerShare, PendingTransfer, PendingTransferReason, Proposal, ProposalAction,
+    ProposalStatus, SessionConfig, SessionKeyAccount, SessionRateLimitConfig, SessionRegistry,
+    SessionRegistryEntry, SessionRequest, SpendingLimit, TemplateAccount, TokenAllowance,
+    TransferTimelock, VaultBalance, VaultConfig, VersionedAccount, WalletAccount, WalletCapacity,
+    WalletInstruction, WalletState, WalletView, WindowUnit,
+};
+use alpha_builder_program_test::utils::{
+    process_instruction, setup_program_test, setup_sbf_program_test,
+};
 use anchor_lang::prelude::*;
-use anchor_lang::AccountDeserialize;
-use solana_program::instruction::AccountMeta;
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anchor_spl::associated_token::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    spl_associated_token_account,
+};
+use anchor_spl::token::{spl_token, TokenAccount};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_interface;
+use solana_program::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_program::program_pack::Pack;
 use solana_program::system_program;
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::account::AccountSharedData;
 use solana_sdk::native_token::LAMPORTS_PER_SOL;
 use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::TransactionError;
+use std::str::FromStr;
+
+async fn init_wallet(
+    ctx: &mut ProgramTestContext,
+    owners: Vec<OwnerShare>,
+    threshold: u16,
+) -> anyhow::Result<(Pubkey, Pubkey)> {
+    let wallet_state = Keypair::new();
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.pubkey().as_ref()],
+        &alpha_builder::ID,
+    );
+
+    let init_accounts = alpha_builder::accounts::InitWallet {
+        payer: ctx.payer.pubkey(),
+        wallet_state: wallet_state.pubkey(),
+        wallet_treasury,
+        system_program: system_program::ID,
+    };
+    let init_ix = program_ix::init_wallet(
+        alpha_builder::ID,
+        init_accounts,
+        owners,
+        threshold,
+        vec![],
+        0,
+        0,
+        WindowUnit::Slots,
+        None,
+        None,
+    );
+    process_instruction(ctx, init_ix, &[&wallet_state]).await?;
+
+    Ok((wallet_state.pubkey(), wallet_treasury))
+}
+
+// Writes a legacy v0 account the way the original program laid it out on chain.
+async fn plant_v0_account<T: Discriminator>(
+    ctx: &mut ProgramTestContext,
+    key: Pubkey,
+    legacy: &impl AnchorSerialize,
+    len: usize,
+) -> anyhow::Result<()> {
+    let mut data = T::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data)?;
+    data.resize(len, 0);
+
+    let rent = ctx.banks_client.get_rent().await?;
+    let mut account = AccountSharedData::new(rent.minimum_balance(len), len, &alpha_builder::ID);
+    account.set_data_from_slice(&data);
+    ctx.set_account(&key, &account);
+    Ok(())
+}
+
+async fn fetch_data(ctx: &mut ProgramTestContext, key: Pubkey) -> anyhow::Result<Vec<u8>> {
+    let account = ctx
+        .banks_client
+        .get_account(key)
+        .await?
+        .expect("account must exist");
+    Ok(account.data)
+}
+
+async fn fetch<T: AccountDeserialize>(
+    ctx: &mut ProgramTestContext,
+    key: Pubkey,
+) -> anyhow::Result<T> {
+    let data = fetch_data(ctx, key).await?;
+    Ok(T::try_deserialize(&mut data.as_slice())?)
+}
+
+// Moves to a fresh slot and pushes the wall clock forward, returning the new timestamp.
+async fn warp_clock(ctx: &mut ProgramTestContext, seconds: i64) -> anyhow::Result<i64> {
+    let slot = ctx.banks_client.get_sysvar::<Clock>().await?.slot;
+    ctx.warp_to_slot(slot + 1)?;
+    let mut clock = ctx.banks_client.get_sysvar::<Clock>().await?;
+    clock.unix_timestamp += seconds;
+    ctx.set_sysvar(&clock);
+    Ok(clock.unix_timestamp)
+}
+
+async fn fund(ctx: &mut ProgramTestContext, target: Pubkey, lamports: u64) -> anyhow::Result<()> {
+    let payer = ctx.payer.pubkey();
+    let transfer_ix = solana_sdk::system_instruction::transfer(&payer, &target, lamports);
+    process_instruction(ctx, transfer_ix, &[]).await
+}
+
+// Fails unless the transaction was rejected with `expected`.
+fn assert_alpha_error(result: anyhow::Result<()>, expected: AlphaError) {
+    let error = result.expect_err("transaction should have failed");
+    match error.downcast_ref::<BanksClientError>() {
+        Some(BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) => assert_eq!(*code, u32::from(expected), "expected {expected:?}"),
+        _ => panic!("expected {expected:?}, got {error}"),
+    }
+}
+
+fn session_pda(wallet_state: &Pubkey, authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"session-key", wallet_state.as_ref(), authority.as_ref()],
+        &alpha_builder::ID,
+    )
+    .0
+}
+
+// No limits beyond the allowed programs; tests fill in the fields they exercise.
+fn session_config(allowed_programs: Vec<Pubkey>) -> SessionConfig {
+    SessionConfig {
+        expires_at: None,
+        usage_limit: None,
+        value_limit: None,
+        allowed_programs,
+        token_allowances: vec![],
+        rate_limit: None,
+        allowed_destinations: vec![],
+        instruction_templates: vec![],
+    }
+}
+
+fn register_session_ix(
+    owner: Pubkey,
+    wallet_state: Pubkey,
+    session_authority: Pubkey,
+    session_registry: Option<Pubkey>,
+    config: SessionConfig,
+) -> Instruction {
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let accounts = alpha_builder::accounts::RegisterSessionKey {
+        authority: owner,
+        wallet_state,
+        wallet_treasury,
+        session_account: session_pda(&wallet_state, &session_authority),
+        session_authority,
+        session_registry,
+        system_program: system_program::ID,
+    };
+    program_ix::register_session_key(alpha_builder::ID, accounts, config)
+}
+
+// Creates a mint the fee payer controls and mints `amount` into the wallet treasury's token account.
+async fn fund_treasury_tokens(
+    ctx: &mut ProgramTestContext,
+    wallet_state: Pubkey,
+    wallet_treasury: Pubkey,
+    amount: u64,
+) -> anyhow::Result<(Pubkey, Pubkey)> {
+    let payer_pubkey = ctx.payer.pubkey();
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await?;
+    let create_mint = solana_sdk::system_instruction::create_account(
+        &payer_pubkey,
+        &mint.pubkey(),
+        rent.minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN as u64,
+        &spl_token::ID,
+    );
+    process_instruction(ctx, create_mint, &[&mint]).await?;
+    let init_mint = spl_token::instruction::initialize_mint(
+        &spl_token::ID,
+        &mint.pubkey(),
+        &payer_pubkey,
+        None,
+        6,
+    )?;
+    process_instruction(ctx, init_mint, &[]).await?;
+
+    let treasury_token_account = get_associated_token_address(&wallet_treasury, &mint.pubkey());
+    let create_accounts = alpha_builder::accounts::CreateTreasuryTokenAccount {
+        payer: payer_pubkey,
+        wallet_state,
+        wallet_treasury,
+        mint: mint.pubkey(),
+        treasury_token_account,
+        token_program: spl_token::ID,
+        associated_token_program: spl_associated_token_account::ID,
+        system_program: system_program::ID,
+    };
+    let create_ix = program_ix::create_treasury_token_account(alpha_builder::ID, create_accounts);
+    process_instruction(ctx, create_ix, &[]).await?;
+
+    let mint_to = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        &mint.pubkey(),
+        &treasury_token_account,
+        &payer_pubkey,
+        &[],
+        amount,
+    )?;
+    process_instruction(ctx, mint_to, &[]).await?;
+
+    Ok((mint.pubkey(), treasury_token_account))
+}
+
+async fn create_token_account(
+    ctx: &mut ProgramTestContext,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> anyhow::Result<Pubkey> {
+    let create_ata = spl_associated_token_account::instruction::create_associated_token_account(
+        &ctx.payer.pubkey(),
+        owner,
+        mint,
+        &spl_token::ID,
+    );
+    process_instruction(ctx, create_ata, &[]).await?;
+    Ok(get_associated_token_address(owner, mint))
+}
+
+async fn token_balance(ctx: &mut ProgramTestContext, account: Pubkey) -> anyhow::Result<u64> {
+    let token_account: TokenAccount = fetch(ctx, account).await?;
+    Ok(token_account.amount)
+}
 
 #[tokio::test]
 async fn vault_deposit_withdraw_flow() -> anyhow::Result<()> {
@@ -65,16 +307,36 @@ async fn vault_deposit_withdraw_flow() -> anyhow::Result<()> {
     let balance_state = VaultBalance::try_deserialize(&mut data_slice)?;
     assert_eq!(balance_state.amount, deposit_amount);
 
-    let withdraw_accounts = alpha_builder::accounts::Withdraw {
-        authority: owner.pubkey(),
-        vault_config,
-        vault_treasury,
-        vault_balance,
-        recipient: owner.pubkey(),
-        system_program: system_program::ID,
+    let withdraw_ix = |amount: u64| {
+        let accounts = alpha_builder::accounts::Withdraw {
+            authority: owner.pubkey(),
+            vault_config,
+            vault_treasury,
+            vault_balance,
+            recipient: owner.pubkey(),
+            system_program: system_program::ID,
+        };
+        program_ix::withdraw(alpha_builder::ID, accounts, amount)
     };
-    let withdraw_ix = program_ix::withdraw(alpha_builder::ID, withdraw_accounts, deposit_amount);
-    process_instruction(&mut ctx, withdraw_ix, &[&owner]).await?;
+
+    // A withdrawal may never take the treasury below its rent-exempt minimum.
+    let treasury = ctx.banks_client.get_account(vault_treasury).await?.unwrap();
+    let mut short_treasury = treasury.clone();
+    short_treasury.lamports -= 1;
+    ctx.set_account(&vault_treasury, &AccountSharedData::from(short_treasury));
+    assert_alpha_error(
+        process_instruction(&mut ctx, withdraw_ix(deposit_amount), &[&owner]).await,
+        AlphaError::InsufficientVaultBalance,
+    );
+    ctx.set_account(&vault_treasury, &AccountSharedData::from(treasury));
+
+    let owner_before = ctx.banks_client.get_balance(owner.pubkey()).await?;
+    process_instruction(&mut ctx, withdraw_ix(1), &[&owner]).await?;
+    process_instruction(&mut ctx, withdraw_ix(deposit_amount - 1), &[&owner]).await?;
+    assert_eq!(
+        ctx.banks_client.get_balance(owner.pubkey()).await?,
+        owner_before + deposit_amount
+    );
 
     let withdrawn_account = ctx
         .banks_client
@@ -132,23 +394,24 @@ async fn wallet_owner_and_session_flow() -> anyhow::Result<()> {
         owners.clone(),
         2,
         guardians,
+        1,
         5,
+        WindowUnit::Slots,
+        None,
         None,
     );
     process_instruction(&mut ctx, init_ix, &[&wallet_state]).await?;
 
-    let top_up = solana_sdk::system_instruction::transfer(
-        &payer_pubkey,
-        &wallet_treasury,
-        3 * LAMPORTS_PER_SOL,
-    );
-    process_instruction(&mut ctx, top_up, &[]).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
 
     let transfer_accounts = alpha_builder::accounts::WalletTransferOwner {
         authority: owner_a.pubkey(),
         wallet_state: wallet_state.pubkey(),
         wallet_treasury,
         destination: recipient.pubkey(),
+        spending_limit: None,
+        address_book: None,
+        pending_transfer: None,
         system_program: system_program::ID,
     };
     let mut owner_transfer_ix =
@@ -158,30 +421,19 @@ async fn wallet_owner_and_session_flow() -> anyhow::Result<()> {
         .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
     process_instruction(&mut ctx, owner_transfer_ix, &[&owner_a, &owner_b]).await?;
 
-    let (session_account, _) = Pubkey::find_program_address(
-        &[
-            b"session-key",
-            wallet_state.pubkey().as_ref(),
-            session_authority.pubkey().as_ref(),
-        ],
-        &alpha_builder::ID,
-    );
-    let session_accounts = alpha_builder::accounts::RegisterSessionKey {
-        authority: owner_a.pubkey(),
-        wallet_state: wallet_state.pubkey(),
-        session_account,
-        payer: owner_a.pubkey(),
-        session_authority: session_authority.pubkey(),
-        system_program: system_program::ID,
-    };
+    let session_account = session_pda(&wallet_state.pubkey(), &session_authority.pubkey());
     let session_config = SessionConfig {
-        expires_at_slot: None,
         usage_limit: Some(1),
         value_limit: Some(LAMPORTS_PER_SOL),
-        allowed_programs: vec![system_program::ID],
+        ..session_config(vec![system_program::ID])
     };
-    let mut register_ix =
-        program_ix::register_session_key(alpha_builder::ID, session_accounts, session_config);
+    let mut register_ix = register_session_ix(
+        owner_a.pubkey(),
+        wallet_state.pubkey(),
+        session_authority.pubkey(),
+        None,
+        session_config,
+    );
     register_ix
         .accounts
         .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
@@ -193,6 +445,9 @@ async fn wallet_owner_and_session_flow() -> anyhow::Result<()> {
         wallet_state: wallet_state.pubkey(),
         wallet_treasury,
         destination: recipient.pubkey(),
+        spending_limit: None,
+        address_book: None,
+        pending_transfer: None,
         system_program: system_program::ID,
     };
     let session_transfer_ix = program_ix::execute_transfer_with_session(
@@ -214,3 +469,3221 @@ async fn wallet_owner_and_session_flow() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn wallet_proposal_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let recipient = Keypair::new();
+
+    for key in [&owner_a, &owner_b] {
+        ctx.banks_client
+            .request_airdrop(key.pubkey(), 5 * LAMPORTS_PER_SOL)
+            .await?;
+    }
+
+    let owners = vec![
+        OwnerShare {
+            owner: owner_a.pubkey(),
+            weight: 1,
+        },
+        OwnerShare {
+            owner: owner_b.pubkey(),
+            weight: 1,
+        },
+    ];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 2).await?;
+
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let (proposal, _) = Pubkey::find_program_address(
+        &[b"proposal", wallet_state.as_ref(), &0u64.to_le_bytes()],
+        &alpha_builder::ID,
+    );
+    let create_accounts = alpha_builder::accounts::CreateProposal {
+        proposer: owner_a.pubkey(),
+        wallet_state,
+        proposal,
+        system_program: system_program::ID,
+    };
+    let create_ix = program_ix::create_proposal(
+        alpha_builder::ID,
+        create_accounts,
+        ProposalAction::Transfer {
+            destination: recipient.pubkey(),
+            amount: LAMPORTS_PER_SOL,
+            memo: None,
+        },
+        1_000,
+    );
+    process_instruction(&mut ctx, create_ix, &[&owner_a]).await?;
+
+    let approve_accounts = alpha_builder::accounts::ProposalVote {
+        owner: owner_b.pubkey(),
+        wallet_state,
+        proposal,
+    };
+    let approve_ix = program_ix::approve_proposal(alpha_builder::ID, approve_accounts);
+    process_instruction(&mut ctx, approve_ix, &[&owner_b]).await?;
+
+    let execute_accounts = alpha_builder::accounts::ExecuteProposal {
+        executor: owner_a.pubkey(),
+        wallet_state,
+        proposal,
+        wallet_treasury,
+        destination: Some(recipient.pubkey()),
+        session_account: None,
+        spending_limit: None,
+        address_book: None,
+        pending_transfer: None,
+        session_registry: None,
+        system_program: system_program::ID,
+    };
+    let execute_ix = program_ix::execute_proposal(alpha_builder::ID, execute_accounts);
+    process_instruction(&mut ctx, execute_ix, &[&owner_a]).await?;
+
+    let proposal_data = ctx
+        .banks_client
+        .get_account(proposal)
+        .await?
+        .expect("proposal account");
+    let mut proposal_slice: &[u8] = &proposal_data.data;
+    let proposal_state = Proposal::try_deserialize(&mut proposal_slice)?;
+    assert_eq!(proposal_state.status, ProposalStatus::Executed);
+    assert_eq!(proposal_state.approvals.len(), 2);
+
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_execute_instructions_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let recipient = Keypair::new();
+    let memo_program = Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr")?;
+
+    let owners = vec![
+        OwnerShare {
+            owner: owner_a.pubkey(),
+            weight: 1,
+        },
+        OwnerShare {
+            owner: owner_b.pubkey(),
+            weight: 1,
+        },
+    ];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 2).await?;
+    let (mint, treasury_token_account) =
+        fund_treasury_tokens(&mut ctx, wallet_state, wallet_treasury, 1_000_000).await?;
+    let recipient_token_account =
+        create_token_account(&mut ctx, &recipient.pubkey(), &mint).await?;
+
+    // The treasury signs an SPL token transfer out of its own token account.
+    let token_transfer = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &treasury_token_account,
+        &recipient_token_account,
+        &wallet_treasury,
+        &[],
+        400_000,
+    )?;
+    let execute_ix = |program_id: Pubkey, cosigners: &[&Keypair]| {
+        let accounts = alpha_builder::accounts::ExecuteInstructions {
+            authority: owner_a.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let instructions = vec![WalletInstruction {
+            program_id,
+            account_indexes: vec![0, 1, 2],
+            data: token_transfer.data.clone(),
+        }];
+        let mut ix = program_ix::execute_instructions(alpha_builder::ID, accounts, instructions);
+        ix.accounts
+            .push(AccountMeta::new(treasury_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new(recipient_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(wallet_treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(spl_token::ID, false));
+        for cosigner in cosigners {
+            ix.accounts
+                .push(AccountMeta::new_readonly(cosigner.pubkey(), true));
+        }
+        ix
+    };
+
+    assert_alpha_error(
+        process_instruction(&mut ctx, execute_ix(spl_token::ID, &[]), &[&owner_a]).await,
+        AlphaError::OwnerThresholdNotMet,
+    );
+    // The wallet can never point the treasury signature back at this program.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            execute_ix(alpha_builder::ID, &[&owner_b]),
+            &[&owner_a, &owner_b],
+        )
+        .await,
+        AlphaError::WalletInstructionNotAllowed,
+    );
+    assert_eq!(token_balance(&mut ctx, recipient_token_account).await?, 0);
+
+    process_instruction(
+        &mut ctx,
+        execute_ix(spl_token::ID, &[&owner_b]),
+        &[&owner_a, &owner_b],
+    )
+    .await?;
+    assert_eq!(
+        token_balance(&mut ctx, recipient_token_account).await?,
+        400_000
+    );
+    assert_eq!(
+        token_balance(&mut ctx, treasury_token_account).await?,
+        600_000
+    );
+
+    // Session rent comes out of the treasury.
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+
+    let session_authority = Keypair::new();
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let session_config = SessionConfig {
+        usage_limit: Some(1),
+        value_limit: Some(0),
+        ..session_config(vec![memo_program])
+    };
+    let mut register_ix = register_session_ix(
+        owner_a.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        session_config,
+    );
+    register_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, register_ix, &[&owner_a, &owner_b]).await?;
+
+    let session_execute_ix = |program_id: Pubkey| {
+        let session_execute_accounts = alpha_builder::accounts::SessionExecute {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let session_instructions = vec![WalletInstruction {
+            program_id,
+            account_indexes: vec![0],
+            data: b"signed through a session key".to_vec(),
+        }];
+        let mut ix = program_ix::execute_with_session(
+            alpha_builder::ID,
+            session_execute_accounts,
+            session_instructions,
+        );
+        ix.accounts
+            .push(AccountMeta::new_readonly(wallet_treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(program_id, false));
+        ix
+    };
+
+    // The token program is not in the session's allowed programs.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(spl_token::ID),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionProgramNotAuthorised,
+    );
+    process_instruction(
+        &mut ctx,
+        session_execute_ix(memo_program),
+        &[&session_authority],
+    )
+    .await?;
+
+    let session_account_data = ctx
+        .banks_client
+        .get_account(session_account)
+        .await?
+        .expect("session account");
+    let mut session_slice: &[u8] = &session_account_data.data;
+    let session_state = SessionKeyAccount::try_deserialize(&mut session_slice)?;
+    assert_eq!(session_state.remaining_calls, Some(0));
+    assert_eq!(session_state.remaining_value, Some(0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_token_transfer_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let recipient = Keypair::new();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    let (mint, treasury_token_account) =
+        fund_treasury_tokens(&mut ctx, wallet_state, wallet_treasury, 1_000_000).await?;
+    let recipient_token_account =
+        create_token_account(&mut ctx, &recipient.pubkey(), &mint).await?;
+
+    let transfer_accounts = alpha_builder::accounts::WalletTokenTransferOwner {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        mint,
+        treasury_token_account,
+        destination: recipient_token_account,
+        token_program: spl_token::ID,
+    };
+    let transfer_ix = program_ix::execute_token_transfer(
+        alpha_builder::ID,
+        transfer_accounts,
+        250_000,
+        Some(b"quest payout".to_vec()),
+    );
+    process_instruction(&mut ctx, transfer_ix, &[&owner]).await?;
+
+    assert_eq!(
+        token_balance(&mut ctx, recipient_token_account).await?,
+        250_000
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_token_cpi_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let recipient = Keypair::new();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+    let (mint, treasury_token_account) =
+        fund_treasury_tokens(&mut ctx, wallet_state, wallet_treasury, 1_000_000).await?;
+    let recipient_token_account =
+        create_token_account(&mut ctx, &recipient.pubkey(), &mint).await?;
+
+    let session_authority = Keypair::new();
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let other_authority = Keypair::new();
+    let other_session = session_pda(&wallet_state, &other_authority.pubkey());
+    let listed_authority = Keypair::new();
+    let listed_session = session_pda(&wallet_state, &listed_authority.pubkey());
+    let unlisted_authority = Keypair::new();
+    let unlisted_session = session_pda(&wallet_state, &unlisted_authority.pubkey());
+    for (authority, allowed_mint, allowed_destinations) in [
+        (&session_authority, mint, vec![]),
+        (&other_authority, Pubkey::new_unique(), vec![]),
+        (&listed_authority, mint, vec![recipient.pubkey()]),
+        (&unlisted_authority, mint, vec![Pubkey::new_unique()]),
+    ] {
+        let config = SessionConfig {
+            token_allowances: vec![TokenAllowance {
+                mint: allowed_mint,
+                amount: 300_000,
+            }],
+            allowed_destinations,
+            ..session_config(vec![spl_token::ID])
+        };
+        let register_ix = register_session_ix(
+            owner.pubkey(),
+            wallet_state,
+            authority.pubkey(),
+            None,
+            config,
+        );
+        process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+    }
+
+    let session_execute_ix = |authority: &Keypair, session: Pubkey, data: Vec<u8>| {
+        let accounts = alpha_builder::accounts::SessionExecute {
+            session_account: session,
+            session_authority: authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let instructions = vec![WalletInstruction {
+            program_id: spl_token::ID,
+            account_indexes: vec![0, 1, 2],
+            data,
+        }];
+        let mut ix = program_ix::execute_with_session(alpha_builder::ID, accounts, instructions);
+        ix.accounts
+            .push(AccountMeta::new(treasury_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new(recipient_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(wallet_treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(spl_token::ID, false));
+        ix
+    };
+    let token_transfer = |amount: u64| -> anyhow::Result<Vec<u8>> {
+        Ok(spl_token::instruction::transfer(
+            &spl_token::ID,
+            &treasury_token_account,
+            &recipient_token_account,
+            &wallet_treasury,
+            &[],
+            amount,
+        )?
+        .data)
+    };
+
+    process_instruction(
+        &mut ctx,
+        session_execute_ix(
+            &session_authority,
+            session_account,
+            token_transfer(200_000)?,
+        ),
+        &[&session_authority],
+    )
+    .await?;
+    assert_eq!(
+        token_balance(&mut ctx, recipient_token_account).await?,
+        200_000
+    );
+    let session_state: SessionKeyAccount = fetch(&mut ctx, session_account).await?;
+    assert_eq!(session_state.token_allowances[0].amount, 100_000);
+
+    // The CPI is charged against the mint's allowance like a direct session token transfer.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(
+                &session_authority,
+                session_account,
+                token_transfer(150_000)?,
+            ),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::InsufficientVaultBalance,
+    );
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(&other_authority, other_session, token_transfer(1)?),
+            &[&other_authority],
+        )
+        .await,
+        AlphaError::SessionMintNotAuthorised,
+    );
+
+    // With an allowlist, every writable account the CPI touches outside the treasury must be listed,
+    // here through the receiving token account's owner.
+    process_instruction(
+        &mut ctx,
+        session_execute_ix(&listed_authority, listed_session, token_transfer(1)?),
+        &[&listed_authority],
+    )
+    .await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(&unlisted_authority, unlisted_session, token_transfer(1)?),
+            &[&unlisted_authority],
+        )
+        .await,
+        AlphaError::SessionDestinationNotAllowed,
+    );
+
+    // Handing the treasury's token account to a delegate would bypass the allowance later.
+    let approve = spl_token::instruction::approve(
+        &spl_token::ID,
+        &treasury_token_account,
+        &recipient_token_account,
+        &wallet_treasury,
+        &[],
+        1,
+    )?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(&session_authority, session_account, approve.data),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionTreasuryModified,
+    );
+    assert_eq!(
+        token_balance(&mut ctx, treasury_token_account).await?,
+        799_999
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_token_2022_cpi_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Keypair::new();
+    let recipient = Pubkey::new_unique();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await?;
+    let create_mint = solana_sdk::system_instruction::create_account(
+        &payer_pubkey,
+        &mint.pubkey(),
+        rent.minimum_balance(spl_token_2022::state::Mint::LEN),
+        spl_token_2022::state::Mint::LEN as u64,
+        &spl_token_2022::ID,
+    );
+    process_instruction(&mut ctx, create_mint, &[&mint]).await?;
+    let init_mint = spl_token_2022::instruction::initialize_mint(
+        &spl_token_2022::ID,
+        &mint.pubkey(),
+        &payer_pubkey,
+        None,
+        6,
+    )?;
+    process_instruction(&mut ctx, init_mint, &[]).await?;
+
+    let mut token_accounts = vec![];
+    for owner in [wallet_treasury, recipient] {
+        let create_ata = spl_associated_token_account::instruction::create_associated_token_account(
+            &payer_pubkey,
+            &owner,
+            &mint.pubkey(),
+            &spl_token_2022::ID,
+        );
+        process_instruction(&mut ctx, create_ata, &[]).await?;
+        token_accounts.push(get_associated_token_address_with_program_id(
+            &owner,
+            &mint.pubkey(),
+            &spl_token_2022::ID,
+        ));
+    }
+    let (treasury_token_account, recipient_token_account) = (token_accounts[0], token_accounts[1]);
+    let mint_to = spl_token_2022::instruction::mint_to(
+        &spl_token_2022::ID,
+        &mint.pubkey(),
+        &treasury_token_account,
+        &payer_pubkey,
+        &[],
+        1_000_000,
+    )?;
+    process_instruction(&mut ctx, mint_to, &[]).await?;
+
+    let session_authority = Keypair::new();
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let other_authority = Keypair::new();
+    let other_session = session_pda(&wallet_state, &other_authority.pubkey());
+    for (authority, allowed_mint) in [
+        (&session_authority, mint.pubkey()),
+        (&other_authority, Pubkey::new_unique()),
+    ] {
+        let config = SessionConfig {
+            token_allowances: vec![TokenAllowance {
+                mint: allowed_mint,
+                amount: 300_000,
+            }],
+            ..session_config(vec![spl_token_2022::ID])
+        };
+        let register_ix = register_session_ix(
+            owner.pubkey(),
+            wallet_state,
+            authority.pubkey(),
+            None,
+            config,
+        );
+        process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+    }
+
+    let session_execute_ix = |authority: &Keypair, session: Pubkey, amount: u64| {
+        let accounts = alpha_builder::accounts::SessionExecute {
+            session_account: session,
+            session_authority: authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let transfer = spl_token_2022::instruction::transfer_checked(
+            &spl_token_2022::ID,
+            &treasury_token_account,
+            &mint.pubkey(),
+            &recipient_token_account,
+            &wallet_treasury,
+            &[],
+            amount,
+            6,
+        )
+        .expect("valid transfer");
+        let instructions = vec![WalletInstruction {
+            program_id: spl_token_2022::ID,
+            account_indexes: vec![0, 1, 2, 3],
+            data: transfer.data,
+        }];
+        let mut ix = program_ix::execute_with_session(alpha_builder::ID, accounts, instructions);
+        ix.accounts
+            .push(AccountMeta::new(treasury_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(mint.pubkey(), false));
+        ix.accounts
+            .push(AccountMeta::new(recipient_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(wallet_treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(spl_token_2022::ID, false));
+        ix
+    };
+
+    // Token-2022 balances draw on the same per-mint allowances as SPL token balances.
+    process_instruction(
+        &mut ctx,
+        session_execute_ix(&session_authority, session_account, 200_000),
+        &[&session_authority],
+    )
+    .await?;
+    let session_state: SessionKeyAccount = fetch(&mut ctx, session_account).await?;
+    assert_eq!(session_state.token_allowances[0].amount, 100_000);
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(&session_authority, session_account, 150_000),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::InsufficientVaultBalance,
+    );
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(&other_authority, other_session, 1),
+            &[&other_authority],
+        )
+        .await,
+        AlphaError::SessionMintNotAuthorised,
+    );
+    let recipient_state: token_interface::TokenAccount =
+        fetch(&mut ctx, recipient_token_account).await?;
+    assert_eq!(recipient_state.amount, 200_000);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_operator_policy_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let operator = Keypair::new();
+    let recipient = Keypair::new();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let add_accounts = alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let operator_config = |scopes: u8| OperatorConfig {
+        scopes,
+        expires_at: None,
+        policy: Some(OperatorPolicyConfig {
+            period_allowance: LAMPORTS_PER_SOL,
+            period_length: 1_000,
+            period_unit: WindowUnit::Slots,
+            max_single_transfer: LAMPORTS_PER_SOL / 2,
+            allowed_destinations: vec![recipient.pubkey()],
+        }),
+    };
+
+    // The policy cannot meter token amounts, so it cannot be combined with the token scope.
+    let token_scoped_ix = program_ix::add_wallet_operator(
+        alpha_builder::ID,
+        alpha_builder::accounts::WalletOwnerAuthority {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        },
+        operator.pubkey(),
+        operator_config(OPERATOR_SCOPE_ALL),
+    );
+    assert_alpha_error(
+        process_instruction(&mut ctx, token_scoped_ix, &[&owner]).await,
+        AlphaError::InvalidOperatorConfig,
+    );
+
+    let add_ix = program_ix::add_wallet_operator(
+        alpha_builder::ID,
+        add_accounts,
+        operator.pubkey(),
+        operator_config(OPERATOR_SCOPE_TRANSFER),
+    );
+    process_instruction(&mut ctx, add_ix, &[&owner]).await?;
+
+    let operator_transfer = |destination: Pubkey, amount: u64| {
+        let accounts = alpha_builder::accounts::OperatorTransfer {
+            operator: operator.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination,
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::operator_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+
+    let oversized_ix = operator_transfer(recipient.pubkey(), LAMPORTS_PER_SOL);
+    assert_alpha_error(
+        process_instruction(&mut ctx, oversized_ix, &[&operator]).await,
+        AlphaError::OperatorTransferTooLarge,
+    );
+
+    let unlisted_ix = operator_transfer(Pubkey::new_unique(), LAMPORTS_PER_SOL / 2);
+    assert_alpha_error(
+        process_instruction(&mut ctx, unlisted_ix, &[&operator]).await,
+        AlphaError::OperatorDestinationNotAllowed,
+    );
+
+    let allowed_ix = operator_transfer(recipient.pubkey(), LAMPORTS_PER_SOL / 2);
+    process_instruction(&mut ctx, allowed_ix, &[&operator]).await?;
+    let allowed_ix = operator_transfer(recipient.pubkey(), 2 * LAMPORTS_PER_SOL / 5);
+    process_instruction(&mut ctx, allowed_ix, &[&operator]).await?;
+
+    // Each transfer is within `max_single_transfer`, but this one runs past the period allowance.
+    let exhausted_ix = operator_transfer(recipient.pubkey(), LAMPORTS_PER_SOL / 5);
+    assert_alpha_error(
+        process_instruction(&mut ctx, exhausted_ix, &[&operator]).await,
+        AlphaError::OperatorAllowanceExceeded,
+    );
+
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, 9 * LAMPORTS_PER_SOL / 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_address_book_delay_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let known = Keypair::new();
+    let unknown = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let (address_book, _) = Pubkey::find_program_address(
+        &[b"address-book", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let set_accounts = alpha_builder::accounts::SetAddressBook {
+        authority: owner.pubkey(),
+        wallet_state,
+        address_book,
+        system_program: system_program::ID,
+    };
+    let set_ix =
+        program_ix::set_address_book(alpha_builder::ID, set_accounts, LAMPORTS_PER_SOL / 10, 50);
+    process_instruction(&mut ctx, set_ix, &[&owner]).await?;
+
+    let add_accounts = alpha_builder::accounts::UpdateAddressBook {
+        authority: owner.pubkey(),
+        wallet_state,
+        address_book,
+    };
+    let add_ix =
+        program_ix::add_address_book_entry(alpha_builder::ID, add_accounts, known.pubkey());
+    process_instruction(&mut ctx, add_ix, &[&owner]).await?;
+
+    let (pending_transfer, _) = Pubkey::find_program_address(
+        &[
+            b"pending-transfer",
+            wallet_state.as_ref(),
+            &0u64.to_le_bytes(),
+        ],
+        &alpha_builder::ID,
+    );
+    let transfer_ix = |destination: Pubkey| {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination,
+            spending_limit: None,
+            address_book: Some(address_book),
+            pending_transfer: Some(pending_transfer),
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer(alpha_builder::ID, accounts, LAMPORTS_PER_SOL, None)
+    };
+
+    process_instruction(&mut ctx, transfer_ix(known.pubkey()), &[&owner]).await?;
+    let known_balance = ctx.banks_client.get_balance(known.pubkey()).await?;
+    assert_eq!(known_balance, LAMPORTS_PER_SOL);
+
+    process_instruction(&mut ctx, transfer_ix(unknown.pubkey()), &[&owner]).await?;
+    let unknown_balance = ctx.banks_client.get_balance(unknown.pubkey()).await?;
+    assert_eq!(unknown_balance, 0);
+
+    let pending_data = ctx
+        .banks_client
+        .get_account(pending_transfer)
+        .await?
+        .expect("pending transfer account");
+    let mut pending_slice: &[u8] = &pending_data.data;
+    let pending_state = PendingTransfer::try_deserialize(&mut pending_slice)?;
+    assert_eq!(pending_state.destination, unknown.pubkey());
+    assert_eq!(pending_state.amount, LAMPORTS_PER_SOL);
+    assert_eq!(
+        pending_state.reason,
+        PendingTransferReason::UnknownDestination
+    );
+
+    let release_ix = || {
+        let accounts = alpha_builder::accounts::ReleasePendingTransfer {
+            wallet_state,
+            wallet_treasury,
+            pending_transfer,
+            destination: unknown.pubkey(),
+            payer: owner.pubkey(),
+            system_program: system_program::ID,
+        };
+        program_ix::release_pending_transfer(alpha_builder::ID, accounts)
+    };
+    assert_alpha_error(
+        process_instruction(&mut ctx, release_ix(), &[]).await,
+        AlphaError::PendingTransferLocked,
+    );
+
+    ctx.warp_to_slot(pending_state.release_slot)?;
+    process_instruction(&mut ctx, release_ix(), &[]).await?;
+
+    let unknown_balance = ctx.banks_client.get_balance(unknown.pubkey()).await?;
+    assert_eq!(unknown_balance, LAMPORTS_PER_SOL);
+    assert!(ctx
+        .banks_client
+        .get_account(pending_transfer)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_transfer_timelock_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+
+    fund(&mut ctx, wallet_treasury, 5 * LAMPORTS_PER_SOL).await?;
+
+    let timelock_accounts = alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let timelock_ix = program_ix::set_transfer_timelock(
+        alpha_builder::ID,
+        timelock_accounts,
+        Some(TransferTimelock {
+            min_amount: 2 * LAMPORTS_PER_SOL,
+            delay_slots: 100,
+        }),
+    );
+    process_instruction(&mut ctx, timelock_ix, &[&owner]).await?;
+
+    let (pending_transfer, _) = Pubkey::find_program_address(
+        &[
+            b"pending-transfer",
+            wallet_state.as_ref(),
+            &0u64.to_le_bytes(),
+        ],
+        &alpha_builder::ID,
+    );
+    let transfer_ix = |amount: u64| {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: Some(pending_transfer),
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+
+    process_instruction(&mut ctx, transfer_ix(LAMPORTS_PER_SOL), &[&owner]).await?;
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    // Lamports parked on the predictable pending-transfer address must not block queueing.
+    let parked = ctx.banks_client.get_rent().await?.minimum_balance(0);
+    let grief = solana_sdk::system_instruction::transfer(&payer_pubkey, &pending_transfer, parked);
+    process_instruction(&mut ctx, grief, &[]).await?;
+
+    process_instruction(&mut ctx, transfer_ix(3 * LAMPORTS_PER_SOL), &[&owner]).await?;
+    let pending_data = ctx
+        .banks_client
+        .get_account(pending_transfer)
+        .await?
+        .expect("pending transfer account");
+    let mut pending_slice: &[u8] = &pending_data.data;
+    let pending_state = PendingTransfer::try_deserialize(&mut pending_slice)?;
+    assert_eq!(pending_state.reason, PendingTransferReason::HighValue);
+    assert_eq!(pending_state.amount, 3 * LAMPORTS_PER_SOL);
+
+    let cancel_accounts = alpha_builder::accounts::CancelPendingTransfer {
+        authority: owner.pubkey(),
+        wallet_state,
+        pending_transfer,
+        payer: owner.pubkey(),
+    };
+    let cancel_ix = program_ix::cancel_pending_transfer(alpha_builder::ID, cancel_accounts);
+    process_instruction(&mut ctx, cancel_ix, &[&owner]).await?;
+
+    assert!(ctx
+        .banks_client
+        .get_account(pending_transfer)
+        .await?
+        .is_none());
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    // Delegates are held to the same timelock as the owners.
+    let operator = Keypair::new();
+    fund(&mut ctx, operator.pubkey(), LAMPORTS_PER_SOL).await?;
+    let add_accounts = alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let add_ix = program_ix::add_wallet_operator(
+        alpha_builder::ID,
+        add_accounts,
+        operator.pubkey(),
+        OperatorConfig {
+            scopes: OPERATOR_SCOPE_ALL,
+            expires_at: None,
+            policy: None,
+        },
+    );
+    process_instruction(&mut ctx, add_ix, &[&owner]).await?;
+
+    let (operator_pending, _) = Pubkey::find_program_address(
+        &[
+            b"pending-transfer",
+            wallet_state.as_ref(),
+            &1u64.to_le_bytes(),
+        ],
+        &alpha_builder::ID,
+    );
+    let operator_accounts = alpha_builder::accounts::OperatorTransfer {
+        operator: operator.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        destination: recipient.pubkey(),
+        spending_limit: None,
+        address_book: None,
+        pending_transfer: Some(operator_pending),
+        system_program: system_program::ID,
+    };
+    let operator_ix = program_ix::operator_transfer(
+        alpha_builder::ID,
+        operator_accounts,
+        2 * LAMPORTS_PER_SOL,
+        None,
+    );
+    process_instruction(&mut ctx, operator_ix, &[&operator]).await?;
+    let pending_state: PendingTransfer = fetch(&mut ctx, operator_pending).await?;
+    assert_eq!(pending_state.reason, PendingTransferReason::HighValue);
+    assert_eq!(pending_state.requested_by, operator.pubkey());
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    // Token moves and invoked instructions are held to `min_amount` in token base units: below it
+    // they go through, at or above it they wait for the timelock to be removed.
+    let (mint, treasury_token_account) = fund_treasury_tokens(
+        &mut ctx,
+        wallet_state,
+        wallet_treasury,
+        3 * LAMPORTS_PER_SOL,
+    )
+    .await?;
+    let recipient_token_account =
+        create_token_account(&mut ctx, &recipient.pubkey(), &mint).await?;
+    let token_ix = |amount: u64| {
+        let accounts = alpha_builder::accounts::WalletTokenTransferOwner {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            mint,
+            treasury_token_account,
+            destination: recipient_token_account,
+            token_program: spl_token::ID,
+        };
+        program_ix::execute_token_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+    process_instruction(&mut ctx, token_ix(1), &[&owner]).await?;
+    assert_alpha_error(
+        process_instruction(&mut ctx, token_ix(2 * LAMPORTS_PER_SOL), &[&owner]).await,
+        AlphaError::TransferTimelockActive,
+    );
+
+    let token_transfer = |amount: u64| -> anyhow::Result<WalletInstruction> {
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &treasury_token_account,
+            &recipient_token_account,
+            &wallet_treasury,
+            &[],
+            amount,
+        )?;
+        Ok(WalletInstruction {
+            program_id: spl_token::ID,
+            account_indexes: vec![0, 1, 2],
+            data: transfer.data,
+        })
+    };
+    let push_token_accounts = |ix: &mut Instruction| {
+        ix.accounts
+            .push(AccountMeta::new(treasury_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new(recipient_token_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(wallet_treasury, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(spl_token::ID, false));
+    };
+    let execute_ix = |instructions: Vec<WalletInstruction>| {
+        let accounts = alpha_builder::accounts::ExecuteInstructions {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let mut ix = program_ix::execute_instructions(alpha_builder::ID, accounts, instructions);
+        push_token_accounts(&mut ix);
+        ix
+    };
+    process_instruction(
+        &mut ctx,
+        execute_ix(vec![token_transfer(1_000)?]),
+        &[&owner],
+    )
+    .await?;
+    // Each instruction stays below the threshold, but together they move `min_amount`.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            execute_ix(vec![
+                token_transfer(LAMPORTS_PER_SOL)?,
+                token_transfer(LAMPORTS_PER_SOL)?,
+            ]),
+            &[&owner],
+        )
+        .await,
+        AlphaError::TransferTimelockActive,
+    );
+
+    let session_authority = Keypair::new();
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let config = SessionConfig {
+        token_allowances: vec![TokenAllowance {
+            mint,
+            amount: 3 * LAMPORTS_PER_SOL,
+        }],
+        ..session_config(vec![spl_token::ID])
+    };
+    let register_ix = register_session_ix(
+        owner.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        config,
+    );
+    process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+    let session_execute_ix = |amount: u64| -> anyhow::Result<Instruction> {
+        let accounts = alpha_builder::accounts::SessionExecute {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let mut ix = program_ix::execute_with_session(
+            alpha_builder::ID,
+            accounts,
+            vec![token_transfer(amount)?],
+        );
+        push_token_accounts(&mut ix);
+        Ok(ix)
+    };
+    process_instruction(&mut ctx, session_execute_ix(500)?, &[&session_authority]).await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_execute_ix(2 * LAMPORTS_PER_SOL)?,
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::TransferTimelockActive,
+    );
+    assert_eq!(
+        token_balance(&mut ctx, recipient_token_account).await?,
+        1 + 1_000 + 500
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_owner_quota_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let owner_c = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner_a.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = [&owner_a, &owner_b, &owner_c]
+        .iter()
+        .map(|owner| OwnerShare {
+            owner: owner.pubkey(),
+            weight: 1,
+        })
+        .collect();
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 2).await?;
+
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let quota_accounts = alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner_a.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let mut quota_ix = program_ix::set_owner_quota(
+        alpha_builder::ID,
+        quota_accounts,
+        owner_a.pubkey(),
+        Some(OwnerQuotaConfig {
+            allowance: LAMPORTS_PER_SOL,
+            period_length: 1_000,
+            period_unit: WindowUnit::Slots,
+        }),
+    );
+    quota_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, quota_ix, &[&owner_a, &owner_b]).await?;
+
+    let transfer_ix = |amount: u64| {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner_a.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+
+    process_instruction(&mut ctx, transfer_ix(LAMPORTS_PER_SOL / 2), &[&owner_a]).await?;
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL / 2);
+
+    assert_alpha_error(
+        process_instruction(&mut ctx, transfer_ix(LAMPORTS_PER_SOL), &[&owner_a]).await,
+        AlphaError::OwnerThresholdNotMet,
+    );
+
+    let mut cosigned_ix = transfer_ix(LAMPORTS_PER_SOL);
+    cosigned_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_c.pubkey(), true));
+    process_instruction(&mut ctx, cosigned_ix, &[&owner_a, &owner_c]).await?;
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_spending_limit_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner_a.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = [&owner_a, &owner_b]
+        .iter()
+        .map(|owner| OwnerShare {
+            owner: owner.pubkey(),
+            weight: 1,
+        })
+        .collect();
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let (spending_limit, _) = Pubkey::find_program_address(
+        &[b"spending-limit", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let limit_accounts = alpha_builder::accounts::SetSpendingLimit {
+        authority: owner_a.pubkey(),
+        wallet_state,
+        spending_limit,
+        system_program: system_program::ID,
+    };
+    let limit_ix = program_ix::set_spending_limit(
+        alpha_builder::ID,
+        limit_accounts,
+        LAMPORTS_PER_SOL,
+        3_600,
+        WindowUnit::Seconds,
+        2,
+    );
+    process_instruction(&mut ctx, limit_ix, &[&owner_a]).await?;
+
+    let transfer_ix = |amount: u64, spending_limit: Option<Pubkey>| {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner_a.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            transfer_ix(LAMPORTS_PER_SOL / 10, None),
+            &[&owner_a],
+        )
+        .await,
+        AlphaError::SpendingLimitAccountMissing,
+    );
+
+    let first = 6 * LAMPORTS_PER_SOL / 10;
+    process_instruction(
+        &mut ctx,
+        transfer_ix(first, Some(spending_limit)),
+        &[&owner_a],
+    )
+    .await?;
+
+    // A single owner cannot take the window past `max_outflow`.
+    let second = LAMPORTS_PER_SOL / 2;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            transfer_ix(second, Some(spending_limit)),
+            &[&owner_a],
+        )
+        .await,
+        AlphaError::SpendingLimitExceeded,
+    );
+
+    // Reaching the elevated threshold overrides the window, and still counts towards it.
+    let mut elevated_ix = transfer_ix(second, Some(spending_limit));
+    elevated_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, elevated_ix, &[&owner_a, &owner_b]).await?;
+    let limit_state: SpendingLimit = fetch(&mut ctx, spending_limit).await?;
+    assert_eq!(limit_state.spent_in_window, first + second);
+
+    // Once the window has elapsed the spent amount starts again from zero.
+    warp_clock(&mut ctx, 3_600).await?;
+    let third = 7 * LAMPORTS_PER_SOL / 10;
+    process_instruction(
+        &mut ctx,
+        transfer_ix(third, Some(spending_limit)),
+        &[&owner_a],
+    )
+    .await?;
+    let limit_state: SpendingLimit = fetch(&mut ctx, spending_limit).await?;
+    assert_eq!(limit_state.spent_in_window, third);
+
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, first + second + third);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_action_threshold_and_roles_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let owner_c = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner_c.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = [&owner_a, &owner_b, &owner_c]
+        .iter()
+        .map(|owner| OwnerShare {
+            owner: owner.pubkey(),
+            weight: 1,
+        })
+        .collect();
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 2).await?;
+
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let authority_accounts = || alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner_a.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let mut threshold_ix = program_ix::set_action_threshold(
+        alpha_builder::ID,
+        authority_accounts(),
+        ActionClass::Transfer,
+        Some(3),
+    );
+    threshold_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, threshold_ix, &[&owner_a, &owner_b]).await?;
+
+    let transfer_ix = |cosigners: &[&Keypair]| {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner_a.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        let mut ix =
+            program_ix::execute_transfer(alpha_builder::ID, accounts, LAMPORTS_PER_SOL, None);
+        for cosigner in cosigners {
+            ix.accounts
+                .push(AccountMeta::new_readonly(cosigner.pubkey(), true));
+        }
+        ix
+    };
+
+    assert_alpha_error(
+        process_instruction(&mut ctx, transfer_ix(&[&owner_b]), &[&owner_a, &owner_b]).await,
+        AlphaError::OwnerThresholdNotMet,
+    );
+    process_instruction(
+        &mut ctx,
+        transfer_ix(&[&owner_b, &owner_c]),
+        &[&owner_a, &owner_b, &owner_c],
+    )
+    .await?;
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    let mut roles_ix = program_ix::set_owner_roles(
+        alpha_builder::ID,
+        authority_accounts(),
+        owner_c.pubkey(),
+        OWNER_ROLE_CANCELLER,
+    );
+    roles_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, roles_ix, &[&owner_a, &owner_b]).await?;
+
+    // Roles live in a section parallel to the owners, so the wallet only holds three of them.
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(
+        wallet.owner_roles(),
+        &[OWNER_ROLE_ALL, OWNER_ROLE_ALL, OWNER_ROLE_CANCELLER]
+    );
+    assert_eq!(data.len(), WalletState::initial_space(3, 0, 0));
+
+    let (proposal, _) = Pubkey::find_program_address(
+        &[b"proposal", wallet_state.as_ref(), &0u64.to_le_bytes()],
+        &alpha_builder::ID,
+    );
+    let create_accounts = alpha_builder::accounts::CreateProposal {
+        proposer: owner_c.pubkey(),
+        wallet_state,
+        proposal,
+        system_program: system_program::ID,
+    };
+    let create_ix = program_ix::create_proposal(
+        alpha_builder::ID,
+        create_accounts,
+        ProposalAction::Transfer {
+            destination: recipient.pubkey(),
+            amount: LAMPORTS_PER_SOL,
+            memo: None,
+        },
+        1_000,
+    );
+    assert_alpha_error(
+        process_instruction(&mut ctx, create_ix, &[&owner_c]).await,
+        AlphaError::OwnerRoleMissing,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn derived_wallet_accepts_prefunded_treasury() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let creator = Keypair::new();
+    let owner = Keypair::new();
+    let recipient = Keypair::new();
+
+    let salt = solana_program::hash::hash(b"new-user@example.com").to_bytes();
+    let (wallet_state, _) = Pubkey::find_program_address(
+        &[b"wallet", creator.pubkey().as_ref(), salt.as_ref()],
+        &alpha_builder::ID,
+    );
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+
+    let prefund = solana_sdk::system_instruction::transfer(
+        &payer_pubkey,
+        &wallet_treasury,
+        2 * LAMPORTS_PER_SOL,
+    );
+    process_instruction(&mut ctx, prefund, &[]).await?;
+
+    let init_accounts = alpha_builder::accounts::InitDerivedWallet {
+        payer: payer_pubkey,
+        creator: creator.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        system_program: system_program::ID,
+    };
+    let init_ix = program_ix::init_derived_wallet(
+        alpha_builder::ID,
+        init_accounts,
+        salt,
+        vec![OwnerShare {
+            owner: owner.pubkey(),
+            weight: 1,
+        }],
+        1,
+        vec![],
+        0,
+        0,
+        WindowUnit::Slots,
+        None,
+        None,
+    );
+    process_instruction(&mut ctx, init_ix, &[&creator]).await?;
+
+    let treasury_balance = ctx.banks_client.get_balance(wallet_treasury).await?;
+    assert!(treasury_balance >= 2 * LAMPORTS_PER_SOL);
+
+    let transfer_accounts = alpha_builder::accounts::WalletTransferOwner {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        destination: recipient.pubkey(),
+        spending_limit: None,
+        address_book: None,
+        pending_transfer: None,
+        system_program: system_program::ID,
+    };
+    let transfer_ix =
+        program_ix::execute_transfer(alpha_builder::ID, transfer_accounts, LAMPORTS_PER_SOL, None);
+    process_instruction(&mut ctx, transfer_ix, &[&owner]).await?;
+
+    let recipient_balance = ctx.banks_client.get_balance(recipient.pubkey()).await?;
+    assert_eq!(recipient_balance, LAMPORTS_PER_SOL);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_close_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let session_authority = Keypair::new();
+    let destination = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+
+    fund(&mut ctx, wallet_treasury, 2 * LAMPORTS_PER_SOL).await?;
+
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let session_config = SessionConfig {
+        value_limit: Some(LAMPORTS_PER_SOL),
+        ..session_config(vec![])
+    };
+    let register_ix = register_session_ix(
+        owner.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        session_config,
+    );
+    process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+
+    let close_ix = |sessions: &[Pubkey]| {
+        let accounts = alpha_builder::accounts::CloseWallet {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            spending_limit: None,
+            address_book: None,
+            session_registry: None,
+            destination: destination.pubkey(),
+            token_program: spl_token::ID,
+        };
+        let mut ix = program_ix::close_wallet(alpha_builder::ID, accounts, 0);
+        for session in sessions {
+            ix.accounts.push(AccountMeta::new(*session, false));
+        }
+        ix
+    };
+
+    assert_alpha_error(
+        process_instruction(&mut ctx, close_ix(&[]), &[&owner]).await,
+        AlphaError::SessionsStillOpen,
+    );
+
+    process_instruction(&mut ctx, close_ix(&[session_account]), &[&owner]).await?;
+
+    for account in [wallet_state, wallet_treasury, session_account] {
+        assert!(ctx.banks_client.get_account(account).await?.is_none());
+    }
+    let destination_balance = ctx.banks_client.get_balance(destination.pubkey()).await?;
+    assert!(destination_balance >= 2 * LAMPORTS_PER_SOL);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_close_open_transfers_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let creator = Keypair::new();
+    let owner = Keypair::new();
+    let known = Keypair::new();
+    let unknown = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    // A derived wallet can be initialised again at the same address once it is closed.
+    let salt = solana_program::hash::hash(b"closing-user@example.com").to_bytes();
+    let (wallet_state, _) = Pubkey::find_program_address(
+        &[b"wallet", creator.pubkey().as_ref(), salt.as_ref()],
+        &alpha_builder::ID,
+    );
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let init_ix = || {
+        let accounts = alpha_builder::accounts::InitDerivedWallet {
+            payer: payer_pubkey,
+            creator: creator.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            system_program: system_program::ID,
+        };
+        program_ix::init_derived_wallet(
+            alpha_builder::ID,
+            accounts,
+            salt,
+            vec![OwnerShare {
+                owner: owner.pubkey(),
+                weight: 1,
+            }],
+            1,
+            vec![],
+            0,
+            0,
+            WindowUnit::Slots,
+            None,
+            None,
+        )
+    };
+    process_instruction(&mut ctx, init_ix(), &[&creator]).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let (address_book, _) = Pubkey::find_program_address(
+        &[b"address-book", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let set_accounts = alpha_builder::accounts::SetAddressBook {
+        authority: owner.pubkey(),
+        wallet_state,
+        address_book,
+        system_program: system_program::ID,
+    };
+    let set_ix =
+        program_ix::set_address_book(alpha_builder::ID, set_accounts, LAMPORTS_PER_SOL / 10, 50);
+    process_instruction(&mut ctx, set_ix, &[&owner]).await?;
+    let add_accounts = alpha_builder::accounts::UpdateAddressBook {
+        authority: owner.pubkey(),
+        wallet_state,
+        address_book,
+    };
+    let add_ix =
+        program_ix::add_address_book_entry(alpha_builder::ID, add_accounts, known.pubkey());
+    process_instruction(&mut ctx, add_ix, &[&owner]).await?;
+
+    let (pending_transfer, _) = Pubkey::find_program_address(
+        &[
+            b"pending-transfer",
+            wallet_state.as_ref(),
+            &0u64.to_le_bytes(),
+        ],
+        &alpha_builder::ID,
+    );
+    let transfer_accounts = alpha_builder::accounts::WalletTransferOwner {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        destination: unknown.pubkey(),
+        spending_limit: None,
+        address_book: Some(address_book),
+        pending_transfer: Some(pending_transfer),
+        system_program: system_program::ID,
+    };
+    let transfer_ix =
+        program_ix::execute_transfer(alpha_builder::ID, transfer_accounts, LAMPORTS_PER_SOL, None);
+    process_instruction(&mut ctx, transfer_ix, &[&owner]).await?;
+    let pending_state: PendingTransfer = fetch(&mut ctx, pending_transfer).await?;
+
+    let (proposal, _) = Pubkey::find_program_address(
+        &[b"proposal", wallet_state.as_ref(), &0u64.to_le_bytes()],
+        &alpha_builder::ID,
+    );
+    let create_accounts = alpha_builder::accounts::CreateProposal {
+        proposer: owner.pubkey(),
+        wallet_state,
+        proposal,
+        system_program: system_program::ID,
+    };
+    let slot = ctx.banks_client.get_sysvar::<Clock>().await?.slot;
+    let create_ix = program_ix::create_proposal(
+        alpha_builder::ID,
+        create_accounts,
+        ProposalAction::Transfer {
+            destination: unknown.pubkey(),
+            amount: LAMPORTS_PER_SOL,
+            memo: None,
+        },
+        slot + 10,
+    );
+    process_instruction(&mut ctx, create_ix, &[&owner]).await?;
+
+    let close_ix = || {
+        let accounts = alpha_builder::accounts::CloseWallet {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            spending_limit: None,
+            address_book: Some(address_book),
+            session_registry: None,
+            destination: known.pubkey(),
+            token_program: spl_token::ID,
+        };
+        program_ix::close_wallet(alpha_builder::ID, accounts, 0)
+    };
+
+    // The release is permissionless, so the wallet may not go away underneath it.
+    assert_alpha_error(
+        process_instruction(&mut ctx, close_ix(), &[&owner]).await,
+        AlphaError::PendingTransfersStillOpen,
+    );
+    let cancel_accounts = alpha_builder::accounts::CancelPendingTransfer {
+        authority: owner.pubkey(),
+        wallet_state,
+        pending_transfer,
+        payer: owner.pubkey(),
+    };
+    let cancel_ix = program_ix::cancel_pending_transfer(alpha_builder::ID, cancel_accounts);
+    process_instruction(&mut ctx, cancel_ix, &[&owner]).await?;
+
+    assert_alpha_error(
+        process_instruction(&mut ctx, close_ix(), &[&owner]).await,
+        AlphaError::ProposalsStillOpen,
+    );
+    ctx.warp_to_slot(slot + 11)?;
+    let close_proposal_accounts = alpha_builder::accounts::CloseProposal {
+        authority: owner.pubkey(),
+        wallet_state,
+        proposal,
+        proposer: owner.pubkey(),
+    };
+    let close_proposal_ix = program_ix::close_proposal(alpha_builder::ID, close_proposal_accounts);
+    process_instruction(&mut ctx, close_proposal_ix, &[&owner]).await?;
+
+    process_instruction(&mut ctx, close_ix(), &[&owner]).await?;
+    assert!(ctx.banks_client.get_account(wallet_state).await?.is_none());
+
+    // Nothing from the closed wallet can be released against its successor.
+    process_instruction(&mut ctx, init_ix(), &[&creator]).await?;
+    fund(&mut ctx, wallet_treasury, 2 * LAMPORTS_PER_SOL).await?;
+    ctx.warp_to_slot(pending_state.release_slot + 1)?;
+    let release_accounts = alpha_builder::accounts::ReleasePendingTransfer {
+        wallet_state,
+        wallet_treasury,
+        pending_transfer,
+        destination: unknown.pubkey(),
+        payer: owner.pubkey(),
+        system_program: system_program::ID,
+    };
+    let release_ix = program_ix::release_pending_transfer(alpha_builder::ID, release_accounts);
+    assert!(process_instruction(&mut ctx, release_ix, &[])
+        .await
+        .is_err());
+    assert_eq!(ctx.banks_client.get_balance(unknown.pubkey()).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_epoch_lifecycle_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let first_authority = Keypair::new();
+    let second_authority = Keypair::new();
+    let recipient = Pubkey::new_unique();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let session_pda = |authority: &Pubkey| session_pda(&wallet_state, authority);
+    let usage_config = |usage_limit: u64| SessionConfig {
+        usage_limit: Some(usage_limit),
+        ..session_config(vec![])
+    };
+    let register_ix = |authority: Pubkey| {
+        register_session_ix(
+            owner.pubkey(),
+            wallet_state,
+            authority,
+            None,
+            usage_config(1),
+        )
+    };
+    let transfer_ix = |authority: Pubkey, amount: u64| {
+        let accounts = alpha_builder::accounts::SessionTransfer {
+            session_account: session_pda(&authority),
+            session_authority: authority,
+            wallet_state,
+            wallet_treasury,
+            destination: recipient,
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer_with_session(alpha_builder::ID, accounts, amount, None)
+    };
+
+    process_instruction(&mut ctx, register_ix(first_authority.pubkey()), &[&owner]).await?;
+    process_instruction(
+        &mut ctx,
+        transfer_ix(first_authority.pubkey(), LAMPORTS_PER_SOL / 10),
+        &[&first_authority],
+    )
+    .await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            transfer_ix(first_authority.pubkey(), LAMPORTS_PER_SOL / 5),
+            &[&first_authority],
+        )
+        .await,
+        AlphaError::SessionKeyExhausted,
+    );
+
+    // Extending refills the limits in place.
+    let extend_accounts = alpha_builder::accounts::UpdateSessionKey {
+        authority: owner.pubkey(),
+        wallet_state,
+        session_account: session_pda(&first_authority.pubkey()),
+        session_registry: None,
+    };
+    let extend_ix = program_ix::extend_session(alpha_builder::ID, extend_accounts, usage_config(3));
+    process_instruction(&mut ctx, extend_ix, &[&owner]).await?;
+    process_instruction(
+        &mut ctx,
+        transfer_ix(first_authority.pubkey(), LAMPORTS_PER_SOL / 5),
+        &[&first_authority],
+    )
+    .await?;
+
+    // Rotation moves the remaining limits to the new authority's session account.
+    let rotate_accounts = alpha_builder::accounts::RotateSessionAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        session_account: session_pda(&first_authority.pubkey()),
+        new_session_account: session_pda(&second_authority.pubkey()),
+        new_session_authority: second_authority.pubkey(),
+        session_registry: None,
+        system_program: system_program::ID,
+    };
+    let rotate_ix = program_ix::rotate_session_authority(alpha_builder::ID, rotate_accounts);
+    process_instruction(&mut ctx, rotate_ix, &[&owner]).await?;
+    assert!(ctx
+        .banks_client
+        .get_account(session_pda(&first_authority.pubkey()))
+        .await?
+        .is_none());
+    let session: SessionKeyAccount =
+        fetch(&mut ctx, session_pda(&second_authority.pubkey())).await?;
+    assert_eq!(session.authority, second_authority.pubkey());
+    assert_eq!(session.remaining_calls, Some(2));
+    assert_eq!(session.epoch, 0);
+    process_instruction(
+        &mut ctx,
+        transfer_ix(second_authority.pubkey(), LAMPORTS_PER_SOL / 4),
+        &[&second_authority],
+    )
+    .await?;
+
+    // Advancing the epoch rejects every older session without touching their accounts.
+    let revoke_accounts = alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let revoke_ix = program_ix::revoke_all_sessions(alpha_builder::ID, revoke_accounts);
+    process_instruction(&mut ctx, revoke_ix, &[&owner]).await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            transfer_ix(second_authority.pubkey(), LAMPORTS_PER_SOL / 3),
+            &[&second_authority],
+        )
+        .await,
+        AlphaError::SessionEpochRevoked,
+    );
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.session_nonce, 1);
+    assert_eq!(wallet.active_sessions, 1);
+
+    // Sessions registered afterwards join the new epoch.
+    process_instruction(&mut ctx, register_ix(first_authority.pubkey()), &[&owner]).await?;
+    process_instruction(
+        &mut ctx,
+        transfer_ix(first_authority.pubkey(), LAMPORTS_PER_SOL / 2),
+        &[&first_authority],
+    )
+    .await?;
+    let session: SessionKeyAccount =
+        fetch(&mut ctx, session_pda(&first_authority.pubkey())).await?;
+    assert_eq!(session.epoch, 1);
+
+    assert_eq!(
+        ctx.banks_client.get_balance(recipient).await?,
+        LAMPORTS_PER_SOL / 10 + LAMPORTS_PER_SOL / 5 + LAMPORTS_PER_SOL / 4 + LAMPORTS_PER_SOL / 2
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_rent_treasury_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    // The owner holds no SOL; the transaction fee payer is a separate key.
+    let owner = Keypair::new();
+    let live_authority = Pubkey::new_unique();
+    let expiring_authority = Pubkey::new_unique();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+    let treasury_funded = ctx.banks_client.get_balance(wallet_treasury).await?;
+
+    let session_pda = |authority: &Pubkey| session_pda(&wallet_state, authority);
+    let register_ix = |authority: Pubkey, expires_at_slot: Option<u64>| {
+        let config = SessionConfig {
+            expires_at: expires_at_slot.map(Deadline::Slot),
+            ..session_config(vec![])
+        };
+        register_session_ix(owner.pubkey(), wallet_state, authority, None, config)
+    };
+    let close_expired_ix = |authority: &Pubkey| {
+        let accounts = alpha_builder::accounts::CloseExpiredSession {
+            wallet_state,
+            wallet_treasury,
+            session_account: session_pda(authority),
+            session_registry: None,
+        };
+        program_ix::close_expired_session(alpha_builder::ID, accounts)
+    };
+
+    process_instruction(&mut ctx, register_ix(live_authority, None), &[&owner]).await?;
+    let session_rent = ctx
+        .banks_client
+        .get_balance(session_pda(&live_authority))
+        .await?;
+    assert!(session_rent > 0);
+    assert_eq!(
+        ctx.banks_client.get_balance(wallet_treasury).await?,
+        treasury_funded - session_rent
+    );
+
+    // A usable session cannot be cleaned up by a third party.
+    assert_alpha_error(
+        process_instruction(&mut ctx, close_expired_ix(&live_authority), &[]).await,
+        AlphaError::SessionStillActive,
+    );
+
+    let revoke_accounts = alpha_builder::accounts::RevokeSessionKey {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        session_account: session_pda(&live_authority),
+        session_registry: None,
+    };
+    let revoke_ix = program_ix::revoke_session_key(alpha_builder::ID, revoke_accounts);
+    process_instruction(&mut ctx, revoke_ix, &[&owner]).await?;
+    assert_eq!(
+        ctx.banks_client.get_balance(wallet_treasury).await?,
+        treasury_funded
+    );
+
+    let slot = ctx.banks_client.get_sysvar::<Clock>().await?.slot;
+    process_instruction(
+        &mut ctx,
+        register_ix(expiring_authority, Some(slot + 5)),
+        &[&owner],
+    )
+    .await?;
+    ctx.warp_to_slot(slot + 10)?;
+
+    // Once expired, anyone can close the session and the rent returns to the treasury.
+    process_instruction(&mut ctx, close_expired_ix(&expiring_authority), &[]).await?;
+    assert!(ctx
+        .banks_client
+        .get_account(session_pda(&expiring_authority))
+        .await?
+        .is_none());
+    assert_eq!(
+        ctx.banks_client.get_balance(wallet_treasury).await?,
+        treasury_funded
+    );
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.active_sessions, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn v0_account_migration_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Pubkey::new_unique();
+    let operator = Pubkey::new_unique();
+    let session_authority = Pubkey::new_unique();
+    let wallet_state = Pubkey::new_unique();
+    let session_account = Pubkey::new_unique();
+    let vault_config = Pubkey::new_unique();
+    let vault_balance = Pubkey::new_unique();
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+
+    let legacy_wallet = v0::WalletState {
+        owners: vec![OwnerShare { owner, weight: 1 }],
+        threshold: 1,
+        guardians: v0::GuardianSet {
+            guardians: vec![],
+            quorum: 0,
+            cooldown_slots: 0,
+            active_recovery: None,
+        },
+        session_nonce: 3,
+        treasury_bump: 254,
+        operator_delegate: Some(operator),
+    };
+    let legacy_wallet_len = v0::WalletState::space(MAX_OWNERS, MAX_GUARDIANS);
+    plant_v0_account::<WalletState>(&mut ctx, wallet_state, &legacy_wallet, legacy_wallet_len)
+        .await?;
+    // Lamports sent to the wallet account leave rent over once it is migrated.
+    fund(&mut ctx, wallet_state, LAMPORTS_PER_SOL).await?;
+
+    let legacy_session = v0::SessionKeyAccount {
+        wallet: wallet_state,
+        authority: session_authority,
+        expires_at_slot: None,
+        remaining_calls: Some(5),
+        remaining_value: Some(LAMPORTS_PER_SOL),
+        allowed_programs: vec![system_program::ID],
+        bump: 255,
+    };
+    plant_v0_account::<SessionKeyAccount>(
+        &mut ctx,
+        session_account,
+        &legacy_session,
+        v0::SessionKeyAccount::space(MAX_SESSION_PROGRAMS),
+    )
+    .await?;
+
+    let legacy_config = v0::VaultConfig {
+        admin: owner,
+        operator: None,
+        bump: 253,
+    };
+    plant_v0_account::<VaultConfig>(&mut ctx, vault_config, &legacy_config, v0::VaultConfig::LEN)
+        .await?;
+
+    let legacy_balance = v0::VaultBalance {
+        owner,
+        amount: 42,
+        bump: 252,
+    };
+    plant_v0_account::<VaultBalance>(
+        &mut ctx,
+        vault_balance,
+        &legacy_balance,
+        v0::VaultBalance::LEN,
+    )
+    .await?;
+
+    // Sessions can only migrate once their wallet is on the current layout.
+    let migrate_session_ix = || {
+        let accounts = alpha_builder::accounts::MigrateSessionKey {
+            payer: payer_pubkey,
+            wallet_state,
+            session_account,
+            wallet_treasury,
+            session_registry: None,
+            system_program: system_program::ID,
+        };
+        program_ix::migrate_session_key(alpha_builder::ID, accounts)
+    };
+    assert_alpha_error(
+        process_instruction(&mut ctx, migrate_session_ix(), &[]).await,
+        AlphaError::UnsupportedAccountVersion,
+    );
+
+    let migrate_accounts = |target: Pubkey| alpha_builder::accounts::MigrateAccount {
+        payer: payer_pubkey,
+        target,
+        system_program: system_program::ID,
+    };
+    let migrate_wallet_ix = || {
+        let accounts = alpha_builder::accounts::MigrateWalletState {
+            payer: payer_pubkey,
+            target: wallet_state,
+            wallet_treasury,
+            system_program: system_program::ID,
+        };
+        program_ix::migrate_wallet_state(alpha_builder::ID, accounts)
+    };
+    let payer_before = ctx.banks_client.get_balance(payer_pubkey).await?;
+    process_instruction(&mut ctx, migrate_wallet_ix(), &[]).await?;
+    // The surplus rent belongs to the wallet, not to whoever cranks the migration.
+    let migrated_len = fetch_data(&mut ctx, wallet_state).await?.len();
+    assert!(migrated_len < legacy_wallet_len);
+    let rent = ctx.banks_client.get_rent().await?;
+    let surplus = rent.minimum_balance(legacy_wallet_len) + LAMPORTS_PER_SOL
+        - rent.minimum_balance(migrated_len);
+    assert_eq!(
+        ctx.banks_client.get_balance(wallet_treasury).await?,
+        LAMPORTS_PER_SOL + surplus
+    );
+    assert!(ctx.banks_client.get_balance(payer_pubkey).await? < payer_before);
+    process_instruction(&mut ctx, migrate_session_ix(), &[]).await?;
+
+    let config_ix =
+        program_ix::migrate_vault_config(alpha_builder::ID, migrate_accounts(vault_config));
+    process_instruction(&mut ctx, config_ix, &[]).await?;
+    let balance_ix =
+        program_ix::migrate_vault_balance(alpha_builder::ID, migrate_accounts(vault_balance));
+    process_instruction(&mut ctx, balance_ix, &[]).await?;
+
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.version, WalletState::VERSION);
+    assert_eq!(wallet.owners(), legacy_wallet.owners.as_slice());
+    assert_eq!(wallet.session_nonce, 3);
+    assert_eq!(wallet.treasury_bump, 254);
+    assert_eq!(wallet.operators().len(), 1);
+    assert_eq!(wallet.operators()[0].operator, operator);
+    assert_eq!(wallet.operators()[0].scopes, OPERATOR_SCOPE_ALL);
+    assert_eq!(wallet.active_sessions, 1);
+
+    let session: SessionKeyAccount = fetch(&mut ctx, session_account).await?;
+    assert_eq!(session.version, SessionKeyAccount::VERSION);
+    assert_eq!(session.authority, session_authority);
+    assert_eq!(session.epoch, 3);
+    assert_eq!(session.remaining_calls, Some(5));
+    assert_eq!(session.allowed_programs, vec![system_program::ID]);
+    assert!(session.token_allowances.is_empty());
+
+    let config: VaultConfig = fetch(&mut ctx, vault_config).await?;
+    assert_eq!(config.version, VaultConfig::VERSION);
+    assert_eq!(config.admin, owner);
+    assert_eq!(config.bump, 253);
+
+    let balance: VaultBalance = fetch(&mut ctx, vault_balance).await?;
+    assert_eq!(balance.version, VaultBalance::VERSION);
+    assert_eq!(balance.amount, 42);
+
+    // Migrating twice is rejected.
+    assert_alpha_error(
+        process_instruction(&mut ctx, migrate_wallet_ix(), &[]).await,
+        AlphaError::AccountAlreadyMigrated,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wallet_right_sizing_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let operator = Keypair::new();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+
+    let initial = ctx.banks_client.get_account(wallet_state).await?.unwrap();
+    assert_eq!(initial.data.len(), WalletState::initial_space(1, 0, 0));
+    assert_eq!(
+        initial.data.len(),
+        WalletView::new(&initial.data).current_space()
+    );
+    assert!(initial.data.len() < WalletState::MAX_LEN);
+
+    // Growth is paid from the treasury, so an unfunded treasury cannot add an operator.
+    let accounts = || alpha_builder::accounts::WalletOwnerAuthority {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let operator_config = OperatorConfig {
+        scopes: OPERATOR_SCOPE_TRANSFER,
+        expires_at: None,
+        policy: Some(OperatorPolicyConfig {
+            period_allowance: LAMPORTS_PER_SOL,
+            period_length: 1_000,
+            period_unit: WindowUnit::Slots,
+            max_single_transfer: LAMPORTS_PER_SOL / 2,
+            allowed_destinations: vec![owner.pubkey()],
+        }),
+    };
+    let add_ix = || {
+        program_ix::add_wallet_operator(
+            alpha_builder::ID,
+            accounts(),
+            operator.pubkey(),
+            operator_config.clone(),
+        )
+    };
+    assert_alpha_error(
+        process_instruction(&mut ctx, add_ix(), &[&owner]).await,
+        AlphaError::TreasuryRentShortfall,
+    );
+
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+    let treasury_before = ctx.banks_client.get_balance(wallet_treasury).await?;
+
+    process_instruction(&mut ctx, add_ix(), &[&owner]).await?;
+    let grown = ctx.banks_client.get_account(wallet_state).await?.unwrap();
+    let wallet = WalletView::new(&grown.data);
+    assert_eq!(grown.data.len(), WalletState::initial_space(1, 0, 1));
+    assert_eq!(grown.data.len(), wallet.current_space());
+    assert!(ctx.banks_client.get_balance(wallet_treasury).await? < treasury_before);
+    let policy = wallet.operators()[0]
+        .policy()
+        .expect("policy must be stored");
+    assert_eq!(policy.allowed_destinations(), &[owner.pubkey()]);
+    assert_eq!(wallet.owners()[0].owner, owner.pubkey());
+
+    let remove_ix =
+        program_ix::remove_wallet_operator(alpha_builder::ID, accounts(), operator.pubkey());
+    process_instruction(&mut ctx, remove_ix, &[&owner]).await?;
+    let shrunk = ctx.banks_client.get_account(wallet_state).await?.unwrap();
+    assert_eq!(shrunk.data.len(), initial.data.len());
+    assert_eq!(shrunk.lamports, initial.lamports);
+    assert_eq!(
+        ctx.banks_client.get_balance(wallet_treasury).await?,
+        treasury_before
+    );
+    assert_eq!(
+        WalletView::new(&shrunk.data).owners()[0].owner,
+        owner.pubkey()
+    );
+
+    // Capacity must cover the owner set and fit the recovery proposal's owner slots.
+    let capacity_ix = |max_owners: u8| {
+        program_ix::set_wallet_capacity(
+            alpha_builder::ID,
+            accounts(),
+            WalletCapacity {
+                max_owners,
+                max_guardians: 0,
+            },
+        )
+    };
+    assert_alpha_error(
+        process_instruction(&mut ctx, capacity_ix(0), &[&owner]).await,
+        AlphaError::InvalidWalletCapacity,
+    );
+    assert_alpha_error(
+        process_instruction(&mut ctx, capacity_ix(32), &[&owner]).await,
+        AlphaError::InvalidWalletCapacity,
+    );
+    process_instruction(&mut ctx, capacity_ix(MAX_OWNERS as u8), &[&owner]).await?;
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.capacity.max_owners, MAX_OWNERS as u8);
+    assert_eq!(wallet.capacity.max_guardians, 0);
+
+    // A proposal opened under a lower capacity still has room for votes once it is raised.
+    process_instruction(&mut ctx, capacity_ix(1), &[&owner]).await?;
+    let (proposal, _) = Pubkey::find_program_address(
+        &[b"proposal", wallet_state.as_ref(), &0u64.to_le_bytes()],
+        &alpha_builder::ID,
+    );
+    let create_ix = program_ix::create_proposal(
+        alpha_builder::ID,
+        alpha_builder::accounts::CreateProposal {
+            proposer: owner.pubkey(),
+            wallet_state,
+            proposal,
+            system_program: system_program::ID,
+        },
+        ProposalAction::Transfer {
+            destination: owner.pubkey(),
+            amount: LAMPORTS_PER_SOL / 4,
+            memo: None,
+        },
+        1_000,
+    );
+    process_instruction(&mut ctx, create_ix, &[&owner]).await?;
+    process_instruction(&mut ctx, capacity_ix(MAX_OWNERS as u8 - 1), &[&owner]).await?;
+
+    let allocated = fetch_data(&mut ctx, proposal).await?.len();
+    let mut voted: Proposal = fetch(&mut ctx, proposal).await?;
+    voted.approvals = (0..MAX_OWNERS).map(|_| Pubkey::new_unique()).collect();
+    voted.rejections = voted.approvals.clone();
+    assert!(8 + voted.try_to_vec()?.len() <= allocated);
+
+    Ok(())
+}
+
+// A single instruction's default allowance; the whole batch has to fit inside it.
+const BATCHED_TRANSFER_CU_BUDGET: u64 = 200_000;
+// Units the same batch consumed with the Borsh wallet layout on an SBF build of the program
+// before the zero-copy change. Until that build has been metered this holds the default budget,
+// so the zero-copy layout must at least stay under it; replace it with the measured figure.
+const BORSH_BATCHED_TRANSFER_CU: u64 = BATCHED_TRANSFER_CU_BUDGET;
+
+#[tokio::test]
+#[ignore = "meters compute units against the SBF build; run with `cargo test-sbf -- --ignored`"]
+async fn wallet_batched_transfer_compute_budget() -> anyhow::Result<()> {
+    // The native processor is not metered, so this only means something against the SBF build.
+    let mut ctx = setup_sbf_program_test()
+        .await
+        .expect("no alpha_builder.so in BPF_OUT_DIR/SBF_OUT_DIR, build it with `cargo build-sbf`");
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Keypair::new();
+    let operator = Keypair::new();
+    let recipient = Pubkey::new_unique();
+
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let transfer_ix = || {
+        let accounts = alpha_builder::accounts::WalletTransferOwner {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient,
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer(alpha_builder::ID, accounts, LAMPORTS_PER_SOL / 2, None)
+    };
+    let add_operator_ix = program_ix::add_wallet_operator(
+        alpha_builder::ID,
+        alpha_builder::accounts::WalletOwnerAuthority {
+            authority: owner.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        },
+        operator.pubkey(),
+        OperatorConfig {
+            scopes: OPERATOR_SCOPE_TRANSFER,
+            expires_at: None,
+            policy: None,
+        },
+    );
+
+    // Simulation reports metered units when the suite runs against the SBF build.
+    let mut transaction = solana_sdk::transaction::Transaction::new_with_payer(
+        &[transfer_ix(), add_operator_ix, transfer_ix(), transfer_ix()],
+        Some(&payer_pubkey),
+    );
+    let recent_blockhash = ctx.banks_client.get_latest_blockhash().await?;
+    transaction.try_sign(&[&ctx.payer, &owner], recent_blockhash)?;
+    let simulation = ctx
+        .banks_client
+        .simulate_transaction(transaction.clone())
+        .await?;
+    assert!(matches!(simulation.result, Some(Ok(()))));
+    let units = simulation
+        .simulation_details
+        .expect("simulation details must be returned")
+        .units_consumed;
+    assert!(units > 0, "SBF build reported no metered compute units");
+    assert!(
+        units <= BATCHED_TRANSFER_CU_BUDGET,
+        "batched transfers consumed {units} compute units"
+    );
+    assert!(
+        units < BORSH_BATCHED_TRANSFER_CU,
+        "batched transfers consumed {units} compute units, Borsh baseline \
+         {BORSH_BATCHED_TRANSFER_CU}"
+    );
+
+    ctx.banks_client.process_transaction(transaction).await?;
+    assert_eq!(
+        ctx.banks_client.get_balance(recipient).await?,
+        3 * (LAMPORTS_PER_SOL / 2)
+    );
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.operator_count, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delegate_freeze_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner_a = Keypair::new();
+    let owner_b = Keypair::new();
+    let guardian = Keypair::new();
+    let operator = Keypair::new();
+    let session_authority = Keypair::new();
+    let recipient = Keypair::new();
+
+    let wallet_keypair = Keypair::new();
+    let wallet_state = wallet_keypair.pubkey();
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let init_accounts = alpha_builder::accounts::InitWallet {
+        payer: payer_pubkey,
+        wallet_state,
+        wallet_treasury,
+        system_program: system_program::ID,
+    };
+    let owners = vec![
+        OwnerShare {
+            owner: owner_a.pubkey(),
+            weight: 1,
+        },
+        OwnerShare {
+            owner: owner_b.pubkey(),
+            weight: 1,
+        },
+    ];
+    let init_ix = program_ix::init_wallet(
+        alpha_builder::ID,
+        init_accounts,
+        owners,
+        2,
+        vec![guardian.pubkey()],
+        1,
+        0,
+        WindowUnit::Slots,
+        Some(operator.pubkey()),
+        None,
+    );
+    process_instruction(&mut ctx, init_ix, &[&wallet_keypair]).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let mut register_ix = register_session_ix(
+        owner_a.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        session_config(vec![]),
+    );
+    register_ix
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, register_ix, &[&owner_a, &owner_b]).await?;
+
+    let session_transfer = |amount: u64| {
+        let accounts = alpha_builder::accounts::SessionTransfer {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer_with_session(alpha_builder::ID, accounts, amount, None)
+    };
+    let operator_transfer = |amount: u64| {
+        let accounts = alpha_builder::accounts::OperatorTransfer {
+            operator: operator.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::operator_transfer(alpha_builder::ID, accounts, amount, None)
+    };
+    let freeze_ix = |authority: Pubkey| {
+        let accounts = alpha_builder::accounts::WalletOwnerAuthority {
+            authority,
+            wallet_state,
+            wallet_treasury,
+        };
+        program_ix::freeze_delegates(alpha_builder::ID, accounts)
+    };
+    let unfreeze_ix = |authority: Pubkey| {
+        let accounts = alpha_builder::accounts::WalletOwnerAuthority {
+            authority,
+            wallet_state,
+            wallet_treasury,
+        };
+        program_ix::unfreeze_delegates(alpha_builder::ID, accounts)
+    };
+
+    // Outsiders cannot freeze, but a single guardian can without the owner threshold.
+    let outsider = Keypair::new();
+    assert_alpha_error(
+        process_instruction(&mut ctx, freeze_ix(outsider.pubkey()), &[&outsider]).await,
+        AlphaError::NotOwnerOrGuardian,
+    );
+    process_instruction(&mut ctx, freeze_ix(guardian.pubkey()), &[&guardian]).await?;
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.delegates_frozen, 1);
+
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(LAMPORTS_PER_SOL / 10),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::DelegatesFrozen,
+    );
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            operator_transfer(LAMPORTS_PER_SOL / 10),
+            &[&operator],
+        )
+        .await,
+        AlphaError::DelegatesFrozen,
+    );
+
+    // Lifting the freeze needs the full owner threshold.
+    assert_alpha_error(
+        process_instruction(&mut ctx, unfreeze_ix(guardian.pubkey()), &[&guardian]).await,
+        AlphaError::OwnerThresholdNotMet,
+    );
+    assert_alpha_error(
+        process_instruction(&mut ctx, unfreeze_ix(owner_a.pubkey()), &[&owner_a]).await,
+        AlphaError::OwnerThresholdNotMet,
+    );
+    let mut unfreeze = unfreeze_ix(owner_a.pubkey());
+    unfreeze
+        .accounts
+        .push(AccountMeta::new_readonly(owner_b.pubkey(), true));
+    process_instruction(&mut ctx, unfreeze, &[&owner_a, &owner_b]).await?;
+
+    process_instruction(
+        &mut ctx,
+        session_transfer(LAMPORTS_PER_SOL / 5),
+        &[&session_authority],
+    )
+    .await?;
+    process_instruction(
+        &mut ctx,
+        operator_transfer(LAMPORTS_PER_SOL / 5),
+        &[&operator],
+    )
+    .await?;
+    assert_eq!(
+        ctx.banks_client.get_balance(recipient.pubkey()).await?,
+        2 * LAMPORTS_PER_SOL / 5
+    );
+
+    // The session authority can drop its own session without any owner signatures.
+    let revoke_accounts = alpha_builder::accounts::RevokeSessionKey {
+        authority: session_authority.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        session_account,
+        session_registry: None,
+    };
+    let revoke_ix = program_ix::revoke_session_key(alpha_builder::ID, revoke_accounts);
+    process_instruction(&mut ctx, revoke_ix, &[&session_authority]).await?;
+    assert!(ctx
+        .banks_client
+        .get_account(session_account)
+        .await?
+        .is_none());
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.active_sessions, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_rate_limit_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let session_authority = Keypair::new();
+    let recipient = Keypair::new();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    // Two calls and half a SOL per 100 slots, with five calls over the session's lifetime.
+    let config = SessionConfig {
+        usage_limit: Some(5),
+        rate_limit: Some(SessionRateLimitConfig {
+            period_length: 100,
+            period_unit: WindowUnit::Slots,
+            calls_per_period: Some(2),
+            value_per_period: Some(LAMPORTS_PER_SOL / 2),
+        }),
+        ..session_config(vec![])
+    };
+    let register_ix = register_session_ix(
+        owner.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        config,
+    );
+    process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+
+    let session_transfer = |amount: u64| {
+        let accounts = alpha_builder::accounts::SessionTransfer {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer_with_session(alpha_builder::ID, accounts, amount, None)
+    };
+    let tenths = |count: u64| count * LAMPORTS_PER_SOL / 10;
+    let hundredths = |count: u64| count * LAMPORTS_PER_SOL / 100;
+
+    process_instruction(&mut ctx, session_transfer(tenths(3)), &[&session_authority]).await?;
+    // Over the period's value budget.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(tenths(3) + 1),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionRateLimited,
+    );
+    process_instruction(&mut ctx, session_transfer(tenths(1)), &[&session_authority]).await?;
+    // Over the period's call budget.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(hundredths(5)),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionRateLimited,
+    );
+
+    let session: SessionKeyAccount = fetch(&mut ctx, session_account).await?;
+    let rate_limit = session.rate_limit.expect("rate limit must be set");
+    assert_eq!(rate_limit.calls_in_period, 2);
+    assert_eq!(rate_limit.spent_in_period, tenths(4));
+    assert_eq!(session.remaining_calls, Some(3));
+
+    // The budget refills once the period has passed.
+    let slot = ctx.banks_client.get_sysvar::<Clock>().await?.slot;
+    ctx.warp_to_slot(slot + 150)?;
+    process_instruction(&mut ctx, session_transfer(tenths(3)), &[&session_authority]).await?;
+    process_instruction(&mut ctx, session_transfer(tenths(2)), &[&session_authority]).await?;
+
+    // The lifetime call count still caps the session across periods.
+    ctx.warp_to_slot(slot + 300)?;
+    process_instruction(
+        &mut ctx,
+        session_transfer(hundredths(15)),
+        &[&session_authority],
+    )
+    .await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(hundredths(7)),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionKeyExhausted,
+    );
+
+    assert_eq!(
+        ctx.banks_client.get_balance(recipient.pubkey()).await?,
+        tenths(9) + hundredths(15)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_destination_and_template_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let session_authority = Keypair::new();
+    let recipient = Keypair::new();
+    let stranger = Keypair::new();
+    let memo_program = Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr")?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, 2 * LAMPORTS_PER_SOL).await?;
+
+    let register_ix = |authority: Pubkey, allowed_programs: Vec<Pubkey>| {
+        // The bot may only pay `recipient` and post "claim:" memos signed by the treasury.
+        let config = SessionConfig {
+            allowed_destinations: vec![recipient.pubkey()],
+            instruction_templates: vec![InstructionTemplate {
+                program_id: memo_program,
+                discriminator: b"claim:".to_vec(),
+                fixed_accounts: vec![TemplateAccount {
+                    position: 0,
+                    pubkey: wallet_treasury,
+                }],
+            }],
+            ..session_config(allowed_programs)
+        };
+        register_session_ix(owner.pubkey(), wallet_state, authority, None, config)
+    };
+
+    // A template cannot name a program the session is not allowed to call.
+    let other_authority = Pubkey::new_unique();
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            register_ix(other_authority, vec![system_program::ID]),
+            &[&owner],
+        )
+        .await,
+        AlphaError::InvalidInstructionTemplate,
+    );
+    process_instruction(
+        &mut ctx,
+        register_ix(
+            session_authority.pubkey(),
+            vec![system_program::ID, memo_program],
+        ),
+        &[&owner],
+    )
+    .await?;
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+
+    let session_transfer = |destination: Pubkey| {
+        let accounts = alpha_builder::accounts::SessionTransfer {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination,
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer_with_session(
+            alpha_builder::ID,
+            accounts,
+            LAMPORTS_PER_SOL / 10,
+            None,
+        )
+    };
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(stranger.pubkey()),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionDestinationNotAllowed,
+    );
+    process_instruction(
+        &mut ctx,
+        session_transfer(recipient.pubkey()),
+        &[&session_authority],
+    )
+    .await?;
+    assert_eq!(
+        ctx.banks_client.get_balance(recipient.pubkey()).await?,
+        LAMPORTS_PER_SOL / 10
+    );
+
+    let memo_ix = |first_account: Pubkey, data: &[u8]| {
+        let accounts = alpha_builder::accounts::SessionExecute {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+        };
+        let instructions = vec![WalletInstruction {
+            program_id: memo_program,
+            account_indexes: vec![0],
+            data: data.to_vec(),
+        }];
+        let mut ix = program_ix::execute_with_session(alpha_builder::ID, accounts, instructions);
+        ix.accounts
+            .push(AccountMeta::new_readonly(first_account, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(memo_program, false));
+        ix
+    };
+
+    process_instruction(
+        &mut ctx,
+        memo_ix(wallet_treasury, b"claim:epoch-1"),
+        &[&session_authority],
+    )
+    .await?;
+    // Wrong discriminator.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            memo_ix(wallet_treasury, b"stake:epoch-1"),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionInstructionNotAllowed,
+    );
+    // Right discriminator, but the fixed account position holds a different key.
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            memo_ix(session_authority.pubkey(), b"claim:epoch-2"),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionInstructionNotAllowed,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_request_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Keypair::new();
+    let device = Keypair::new();
+    let other_device = Keypair::new();
+    let stranger = Keypair::new();
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+
+    let request_pda = |authority: &Pubkey| {
+        Pubkey::find_program_address(
+            &[
+                b"session-request",
+                wallet_state.as_ref(),
+                authority.as_ref(),
+            ],
+            &alpha_builder::ID,
+        )
+        .0
+    };
+    let session_pda = |authority: &Pubkey| session_pda(&wallet_state, authority);
+    let usage_config = |usage_limit: u64| SessionConfig {
+        usage_limit: Some(usage_limit),
+        ..session_config(vec![])
+    };
+    let request_ix = |authority: Pubkey| {
+        let accounts = alpha_builder::accounts::RequestSession {
+            payer: payer_pubkey,
+            session_authority: authority,
+            wallet_state,
+            session_request: request_pda(&authority),
+            system_program: system_program::ID,
+        };
+        program_ix::request_session(alpha_builder::ID, accounts, usage_config(10))
+    };
+    let reject_ix = |authority: Pubkey, requester: &Pubkey| {
+        let accounts = alpha_builder::accounts::RejectSessionRequest {
+            authority,
+            wallet_state,
+            session_request: request_pda(requester),
+            payer: payer_pubkey,
+        };
+        program_ix::reject_session_request(alpha_builder::ID, accounts)
+    };
+
+    // The new device asks for a session; only its own key needs to sign.
+    process_instruction(&mut ctx, request_ix(device.pubkey()), &[&device]).await?;
+    let request: SessionRequest = fetch(&mut ctx, request_pda(&device.pubkey())).await?;
+    assert_eq!(request.wallet, wallet_state);
+    assert_eq!(request.authority, device.pubkey());
+    assert_eq!(request.config, usage_config(10));
+
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            reject_ix(stranger.pubkey(), &device.pubkey()),
+            &[&stranger],
+        )
+        .await,
+        AlphaError::NotWalletOwner,
+    );
+
+    // The owner approves with a tighter call limit than requested.
+    let approve_accounts = alpha_builder::accounts::ApproveSessionRequest {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        session_request: request_pda(&device.pubkey()),
+        payer: payer_pubkey,
+        session_account: session_pda(&device.pubkey()),
+        session_registry: None,
+        system_program: system_program::ID,
+    };
+    let approve_ix = program_ix::approve_session_request(
+        alpha_builder::ID,
+        approve_accounts,
+        Some(usage_config(3)),
+    );
+    process_instruction(&mut ctx, approve_ix, &[&owner]).await?;
+
+    let session: SessionKeyAccount = fetch(&mut ctx, session_pda(&device.pubkey())).await?;
+    assert_eq!(session.authority, device.pubkey());
+    assert_eq!(session.remaining_calls, Some(3));
+    assert!(ctx
+        .banks_client
+        .get_account(request_pda(&device.pubkey()))
+        .await?
+        .is_none());
+
+    // A rejected request is closed without creating a session.
+    process_instruction(
+        &mut ctx,
+        request_ix(other_device.pubkey()),
+        &[&other_device],
+    )
+    .await?;
+    process_instruction(
+        &mut ctx,
+        reject_ix(owner.pubkey(), &other_device.pubkey()),
+        &[&owner],
+    )
+    .await?;
+    assert!(ctx
+        .banks_client
+        .get_account(request_pda(&other_device.pubkey()))
+        .await?
+        .is_none());
+    assert!(ctx
+        .banks_client
+        .get_account(session_pda(&other_device.pubkey()))
+        .await?
+        .is_none());
+
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.active_sessions, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_registry_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let owner = Keypair::new();
+    let first_authority = Pubkey::new_unique();
+    let second_authority = Pubkey::new_unique();
+    ctx.banks_client
+        .request_airdrop(owner.pubkey(), LAMPORTS_PER_SOL)
+        .await?;
+
+    let owners = vec![OwnerShare {
+        owner: owner.pubkey(),
+        weight: 1,
+    }];
+    let (wallet_state, wallet_treasury) = init_wallet(&mut ctx, owners, 1).await?;
+    fund(&mut ctx, wallet_treasury, LAMPORTS_PER_SOL).await?;
+
+    let (session_registry, _) = Pubkey::find_program_address(
+        &[b"session-registry", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let session_pda = |authority: &Pubkey| session_pda(&wallet_state, authority);
+    let register_ix = |authority: Pubkey, registry: Option<Pubkey>| {
+        register_session_ix(
+            owner.pubkey(),
+            wallet_state,
+            authority,
+            registry,
+            session_config(vec![]),
+        )
+    };
+    let init_registry_ix = |sessions: &[Pubkey]| {
+        let accounts = alpha_builder::accounts::InitSessionRegistry {
+            authority: owner.pubkey(),
+            wallet_state,
+            session_registry,
+            system_program: system_program::ID,
+        };
+        let mut ix = program_ix::init_session_registry(alpha_builder::ID, accounts);
+        for session in sessions {
+            ix.accounts.push(AccountMeta::new_readonly(*session, false));
+        }
+        ix
+    };
+
+    process_instruction(&mut ctx, register_ix(first_authority, None), &[&owner]).await?;
+
+    // The registry must start out listing every open session.
+    assert_alpha_error(
+        process_instruction(&mut ctx, init_registry_ix(&[]), &[&owner]).await,
+        AlphaError::SessionRegistryIncomplete,
+    );
+    process_instruction(
+        &mut ctx,
+        init_registry_ix(&[session_pda(&first_authority)]),
+        &[&owner],
+    )
+    .await?;
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.session_registry_enabled, 1);
+
+    // Once it exists, session changes cannot skip it.
+    assert_alpha_error(
+        process_instruction(&mut ctx, register_ix(second_authority, None), &[&owner]).await,
+        AlphaError::SessionRegistryAccountMissing,
+    );
+    process_instruction(
+        &mut ctx,
+        register_ix(second_authority, Some(session_registry)),
+        &[&owner],
+    )
+    .await?;
+    let registry: SessionRegistry = fetch(&mut ctx, session_registry).await?;
+    assert_eq!(registry.entries.len(), 2);
+
+    let extend_accounts = alpha_builder::accounts::UpdateSessionKey {
+        authority: owner.pubkey(),
+        wallet_state,
+        session_account: session_pda(&first_authority),
+        session_registry: Some(session_registry),
+    };
+    let extend_ix = program_ix::extend_session(
+        alpha_builder::ID,
+        extend_accounts,
+        SessionConfig {
+            expires_at: Some(Deadline::Slot(1_000_000)),
+            ..session_config(vec![])
+        },
+    );
+    process_instruction(&mut ctx, extend_ix, &[&owner]).await?;
+
+    let revoke_accounts = alpha_builder::accounts::RevokeSessionKey {
+        authority: owner.pubkey(),
+        wallet_state,
+        wallet_treasury,
+        session_account: session_pda(&second_authority),
+        session_registry: Some(session_registry),
+    };
+    let revoke_ix = program_ix::revoke_session_key(alpha_builder::ID, revoke_accounts);
+    process_instruction(&mut ctx, revoke_ix, &[&owner]).await?;
+
+    let registry: SessionRegistry = fetch(&mut ctx, session_registry).await?;
+    assert_eq!(
+        registry.entries,
+        vec![SessionRegistryEntry {
+            authority: first_authority,
+            epoch: 0,
+            expires_at: Some(Deadline::Slot(1_000_000)),
+        }]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wall_clock_deadline_flow() -> anyhow::Result<()> {
+    let mut ctx = setup_program_test().await;
+    let payer_pubkey = ctx.payer.pubkey();
+    let owner = Keypair::new();
+    let guardian = Keypair::new();
+    let new_owner = Keypair::new();
+    let session_authority = Keypair::new();
+    let recipient = Keypair::new();
+
+    let wallet_keypair = Keypair::new();
+    let wallet_state = wallet_keypair.pubkey();
+    let (wallet_treasury, _) = Pubkey::find_program_address(
+        &[b"wallet-treasury", wallet_state.as_ref()],
+        &alpha_builder::ID,
+    );
+    let init_accounts = alpha_builder::accounts::InitWallet {
+        payer: payer_pubkey,
+        wallet_state,
+        wallet_treasury,
+        system_program: system_program::ID,
+    };
+    let init_ix = program_ix::init_wallet(
+        alpha_builder::ID,
+        init_accounts,
+        vec![OwnerShare {
+            owner: owner.pubkey(),
+            weight: 1,
+        }],
+        1,
+        vec![guardian.pubkey()],
+        1,
+        3_600,
+        WindowUnit::Seconds,
+        None,
+        None,
+    );
+    process_instruction(&mut ctx, init_ix, &[&wallet_keypair]).await?;
+    fund(&mut ctx, wallet_treasury, 3 * LAMPORTS_PER_SOL).await?;
+
+    // A session that expires ten minutes from now, regardless of how many slots that takes.
+    let session_account = session_pda(&wallet_state, &session_authority.pubkey());
+    let now = ctx.banks_client.get_sysvar::<Clock>().await?.unix_timestamp;
+    let config = SessionConfig {
+        expires_at: Some(Deadline::Timestamp(now + 600)),
+        ..session_config(vec![])
+    };
+    let register_ix = register_session_ix(
+        owner.pubkey(),
+        wallet_state,
+        session_authority.pubkey(),
+        None,
+        config,
+    );
+    process_instruction(&mut ctx, register_ix, &[&owner]).await?;
+    let session: SessionKeyAccount = fetch(&mut ctx, session_account).await?;
+    assert_eq!(session.expires_at, Some(Deadline::Timestamp(now + 600)));
+
+    let session_transfer = |amount: u64| {
+        let accounts = alpha_builder::accounts::SessionTransfer {
+            session_account,
+            session_authority: session_authority.pubkey(),
+            wallet_state,
+            wallet_treasury,
+            destination: recipient.pubkey(),
+            spending_limit: None,
+            address_book: None,
+            pending_transfer: None,
+            system_program: system_program::ID,
+        };
+        program_ix::execute_transfer_with_session(alpha_builder::ID, accounts, amount, None)
+    };
+    warp_clock(&mut ctx, 300).await?;
+    process_instruction(
+        &mut ctx,
+        session_transfer(LAMPORTS_PER_SOL / 10),
+        &[&session_authority],
+    )
+    .await?;
+    warp_clock(&mut ctx, 301).await?;
+    assert_alpha_error(
+        process_instruction(
+            &mut ctx,
+            session_transfer(LAMPORTS_PER_SOL / 5),
+            &[&session_authority],
+        )
+        .await,
+        AlphaError::SessionKeyExpired,
+    );
+
+    // Guardian recovery waits out an hour of wall-clock time.
+    let guardian_accounts = || alpha_builder::accounts::GuardianAction {
+        guardian: guardian.pubkey(),
+        wallet_state,
+        wallet_treasury,
+    };
+    let new_owners = vec![OwnerShare {
+        owner: new_owner.pubkey(),
+        weight: 1,
+    }];
+    let initiate_ix = program_ix::guardian_initiate_recovery(
+        alpha_builder::ID,
+        guardian_accounts(),
+        1,
+        new_owners.clone(),
+    );
+    process_instruction(&mut ctx, initiate_ix, &[&guardian]).await?;
+    let proposed_at = ctx.banks_client.get_sysvar::<Clock>().await?.unix_timestamp;
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(
+        wallet.recovery_deadline(),
+        Some(Deadline::Timestamp(proposed_at + 3_600))
+    );
+
+    warp_clock(&mut ctx, 3_599).await?;
+    let execute_ix = program_ix::guardian_execute_recovery(alpha_builder::ID, guardian_accounts());
+    assert_alpha_error(
+        process_instruction(&mut ctx, execute_ix, &[&guardian]).await,
+        AlphaError::RecoveryNotReady,
+    );
+
+    warp_clock(&mut ctx, 1).await?;
+    let execute_ix = program_ix::guardian_execute_recovery(alpha_builder::ID, guardian_accounts());
+    process_instruction(&mut ctx, execute_ix, &[&guardian]).await?;
+    let data = fetch_data(&mut ctx, wallet_state).await?;
+    let wallet = WalletView::new(&data);
+    assert_eq!(wallet.owners(), new_owners.as_slice());
+    assert!(wallet.active_recovery().is_none());
+
+    Ok(())
+}